@@ -0,0 +1,80 @@
+use crate::generic_error::GenericError;
+use hound::{SampleFormat, WavSpec, WavWriter};
+use std::{fs::File, io::BufWriter};
+
+/// Rate of the SPU output
+pub const SAMPLE_RATE: u32 = 44100;
+
+/// Consumer of the SPU's stereo output
+pub trait SampleSink {
+    /// Receives the next samples, as (left, right) pairs.
+    fn write_samples(&mut self, samples: &[(i16, i16)]) -> Result<(), GenericError>;
+}
+
+/// Streams the output to a 16-bit stereo WAV file
+pub struct WavSink {
+    writer: WavWriter<BufWriter<File>>,
+}
+
+impl WavSink {
+    pub fn new(path: &str) -> Result<Self, GenericError> {
+        let spec = WavSpec {
+            channels: 2,
+            sample_rate: SAMPLE_RATE,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+
+        match WavWriter::create(path, spec) {
+            Ok(writer) => Ok(Self { writer }),
+            Err(err) => Err(GenericError {
+                message: format!("WAV_CREATE_FAILED ({})", err),
+            }),
+        }
+    }
+}
+
+impl SampleSink for WavSink {
+    fn write_samples(&mut self, samples: &[(i16, i16)]) -> Result<(), GenericError> {
+        for &(left, right) in samples {
+            let result = self
+                .writer
+                .write_sample(left)
+                .and_then(|_| self.writer.write_sample(right));
+
+            if let Err(err) = result {
+                return Err(GenericError {
+                    message: format!("WAV_WRITE_FAILED ({})", err),
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hound::WavReader;
+
+    #[test]
+    fn wav_sink_writes_interleaved_stereo() {
+        let path = std::env::temp_dir().join("rs1_wav_sink_test.wav");
+        let path = path.to_str().unwrap();
+
+        {
+            let mut sink = WavSink::new(path).unwrap();
+            sink.write_samples(&[(1, -1), (0x7fff, -0x8000)]).unwrap();
+        }
+
+        let mut reader = WavReader::open(path).unwrap();
+        let spec = reader.spec();
+        let samples: Vec<i16> = reader.samples().map(Result::unwrap).collect();
+
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!((spec.channels, spec.sample_rate), (2, SAMPLE_RATE));
+        assert_eq!(samples, [1, -1, 0x7fff, -0x8000]);
+    }
+}
@@ -0,0 +1,118 @@
+use crate::{
+    audio::{SampleSink, SAMPLE_RATE},
+    generic_error::GenericError,
+};
+use cpal::{
+    traits::{DeviceTrait, HostTrait, StreamTrait},
+    BufferSize, SampleRate, Stream, StreamConfig,
+};
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+/// Samples the ring buffer holds before dropping the oldest, 250 ms
+const RING_CAPACITY: usize = SAMPLE_RATE as usize / 4;
+
+/// Buffered samples above which emulation waits for playback, about 50 ms
+const HIGH_WATERMARK: usize = SAMPLE_RATE as usize / 20;
+
+type RingBuffer = Arc<Mutex<VecDeque<(i16, i16)>>>;
+
+fn audio_error<E: std::fmt::Display>(message: &str, err: E) -> GenericError {
+    GenericError {
+        message: format!("{} ({})", message, err),
+    }
+}
+
+/// Plays the output on the default audio device
+pub struct LiveAudio {
+    buffer: RingBuffer,
+    _stream: Stream, // Playback stops when dropped
+}
+
+impl LiveAudio {
+    pub fn new() -> Result<Self, GenericError> {
+        let device = match cpal::default_host().default_output_device() {
+            Some(device) => device,
+            None => {
+                return Err(GenericError {
+                    message: "NO_AUDIO_OUTPUT_DEVICE".to_owned(),
+                })
+            }
+        };
+        let config = StreamConfig {
+            channels: 2,
+            sample_rate: SampleRate(SAMPLE_RATE),
+            buffer_size: BufferSize::Default,
+        };
+
+        let buffer: RingBuffer = Arc::new(Mutex::new(VecDeque::with_capacity(RING_CAPACITY)));
+        let playing = buffer.clone();
+
+        let stream = device
+            .build_output_stream(
+                &config,
+                move |data: &mut [f32], _| {
+                    let mut buffer = playing.lock().unwrap();
+
+                    for frame in data.chunks_mut(2) {
+                        // Silence on underruns
+                        let (left, right) = buffer.pop_front().unwrap_or((0, 0));
+
+                        frame[0] = left as f32 / 32768.0;
+                        frame[1] = right as f32 / 32768.0;
+                    }
+                },
+                |err| eprintln!("Audio stream error: {}", err),
+                None,
+            )
+            .map_err(|err| audio_error("AUDIO_STREAM_BUILD_FAILED", err))?;
+
+        stream
+            .play()
+            .map_err(|err| audio_error("AUDIO_STREAM_PLAY_FAILED", err))?;
+
+        Ok(Self {
+            buffer,
+            _stream: stream,
+        })
+    }
+
+    /// Sink feeding the ring buffer.
+    pub fn sink(&self) -> RingBufferSink {
+        RingBufferSink {
+            buffer: self.buffer.clone(),
+        }
+    }
+
+    /// Blocks while playback is well behind emulation, so emulation runs at
+    /// the speed of the audio device.
+    pub fn wait_for_room(&self) {
+        while self.buffer.lock().unwrap().len() > HIGH_WATERMARK {
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
+
+pub struct RingBufferSink {
+    buffer: RingBuffer,
+}
+
+impl SampleSink for RingBufferSink {
+    fn write_samples(&mut self, samples: &[(i16, i16)]) -> Result<(), GenericError> {
+        let mut buffer = self.buffer.lock().unwrap();
+
+        for &sample in samples {
+            if buffer.len() == RING_CAPACITY {
+                buffer.pop_front();
+            }
+
+            buffer.push_back(sample);
+        }
+
+        Ok(())
+    }
+}
@@ -9,7 +9,7 @@ use std::{
 const BIOS_SIZE: usize = 524288;
 
 /// BIOS image
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct Bios {
     /// Memory data
     pub data: Vec<u8>,
@@ -31,17 +31,22 @@ impl Bios {
 
     pub fn load32(&self, offset: u32) -> u32 {
         let offset = offset as usize;
-        let b0 = self.data[offset + 0] as u32;
+        let b0 = self.data[offset] as u32;
         let b1 = self.data[offset + 1] as u32;
         let b2 = self.data[offset + 2] as u32;
         let b3 = self.data[offset + 3] as u32;
         b0 | (b1 << 8) | (b2 << 16) | (b3 << 24)
     }
-}
 
-impl Default for Bios {
-    fn default() -> Self {
-        Bios { data: Vec::new() }
+    pub fn load16(&self, offset: u32) -> u16 {
+        let offset = offset as usize;
+        let b0 = self.data[offset] as u16;
+        let b1 = self.data[offset + 1] as u16;
+        b0 | (b1 << 8)
+    }
+
+    pub fn load8(&self, offset: u32) -> u8 {
+        self.data[offset as usize]
     }
 }
 
@@ -0,0 +1,350 @@
+/// Exception causes, as stored in the `ExcCode` field of CAUSE.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exception {
+    Interrupt = 0x0,
+    LoadAddressError = 0x4,
+    StoreAddressError = 0x5,
+    InstructionBusError = 0x6,
+    DataBusError = 0x7,
+    Syscall = 0x8,
+    Break = 0x9,
+    ReservedInstruction = 0xa,
+    CoprocessorUnusable = 0xb,
+    Overflow = 0xc,
+}
+
+/// Cop0 register indices
+pub const BPC: u32 = 3; // Breakpoint on execute
+pub const BDA: u32 = 5; // Breakpoint on data access
+pub const JUMPDEST: u32 = 6; // Randomly memorized jump address
+pub const DCIC: u32 = 7; // Breakpoint control
+pub const BADVADDR: u32 = 8; // Bad virtual address
+pub const BDAM: u32 = 9; // Data access breakpoint mask
+pub const BPCM: u32 = 11; // Execute breakpoint mask
+pub const SR: u32 = 12; // Status register
+pub const CAUSE: u32 = 13; // Exception cause
+pub const EPC: u32 = 14; // Return address from trap
+pub const PRID: u32 = 15; // Processor ID
+
+/// SR bits
+pub const SR_IEC: u32 = 1 << 0; // Current interrupt enable
+pub const SR_KUC: u32 = 1 << 1; // Current user mode
+pub const SR_ISC: u32 = 1 << 16; // Isolate cache
+pub const SR_BEV: u32 = 1 << 22; // Boot exception vectors in ROM
+pub const SR_CU0: u32 = 1 << 28; // COP0 usable in user mode
+pub const SR_CU2: u32 = 1 << 30; // COP2 (GTE) enable
+
+/// CAUSE bits
+const CAUSE_SOFTWARE_MASK: u32 = 0x300; // The only writable bits
+const CAUSE_HARDWARE_INTERRUPT: u32 = 1 << 10; // Interrupt controller line
+const CAUSE_CE_SHIFT: u32 = 28; // Coprocessor number of a coprocessor unusable exception
+const CAUSE_BD: u32 = 1 << 31; // Exception happened in a branch delay slot
+
+/// System control coprocessor
+#[derive(Debug, Clone)]
+pub struct Cop0 {
+    pub bpc: u32,
+    pub bda: u32,
+    pub jumpdest: u32,
+    pub dcic: u32,
+    pub badvaddr: u32,
+    pub bdam: u32,
+    pub bpcm: u32,
+    pub sr: u32,
+    pub cause: u32,
+    pub epc: u32,
+}
+
+impl Cop0 {
+    pub fn new() -> Self {
+        Self {
+            bpc: 0,
+            bda: 0,
+            jumpdest: 0,
+            dcic: 0,
+            badvaddr: 0,
+            bdam: 0,
+            bpcm: 0,
+            sr: SR_BEV, // Exceptions go to the ROM vectors until the BIOS says otherwise
+            cause: 0,
+            epc: 0,
+        }
+    }
+
+    /// Reads a register, or returns `None` if it does not exist (reserved
+    /// instruction).
+    pub fn read(&self, index: u32) -> Option<u32> {
+        match index {
+            BPC => Some(self.bpc),
+            BDA => Some(self.bda),
+            JUMPDEST => Some(self.jumpdest),
+            DCIC => Some(self.dcic),
+            BADVADDR => Some(self.badvaddr),
+            BDAM => Some(self.bdam),
+            BPCM => Some(self.bpcm),
+            SR => Some(self.sr),
+            CAUSE => Some(self.cause),
+            EPC => Some(self.epc),
+            PRID => Some(0x00000002),
+            0..=15 => Some(0), // Unused registers read back as garbage
+            _ => None,
+        }
+    }
+
+    /// Writes a register. Read-only registers silently ignore the write.
+    pub fn write(&mut self, index: u32, value: u32) {
+        match index {
+            BPC => self.bpc = value,
+            BDA => self.bda = value,
+            DCIC => self.dcic = value,
+            BDAM => self.bdam = value,
+            BPCM => self.bpcm = value,
+            SR => self.sr = value,
+            CAUSE => {
+                self.cause = (self.cause & !CAUSE_SOFTWARE_MASK) | (value & CAUSE_SOFTWARE_MASK)
+            }
+            _ => (),
+        }
+    }
+
+    /// Records an exception and returns the address of its handler.
+    pub fn enter_exception(&mut self, cause: Exception, pc: u32, in_delay_slot: bool) -> u32 {
+        // Push a new (kernel mode, interrupts disabled) entry onto the
+        // three-level KU/IE stack in SR[5:0]
+        let mode = self.sr & 0x3f;
+        self.sr = (self.sr & !0x3f) | ((mode << 2) & 0x3f);
+
+        self.cause = (self.cause & !0x7c) | ((cause as u32) << 2);
+
+        // When the exception hits a delay slot, EPC points at the branch so
+        // that it is executed again on return.
+        if in_delay_slot {
+            self.epc = pc.wrapping_sub(4);
+            self.cause |= CAUSE_BD;
+        } else {
+            self.epc = pc;
+            self.cause &= !CAUSE_BD;
+        }
+
+        if self.sr & SR_BEV != 0 {
+            0xbfc00180
+        } else {
+            0x80000080
+        }
+    }
+
+    /// Pops the KU/IE stack (`rfe`).
+    pub fn return_from_exception(&mut self) {
+        let mode = self.sr & 0x3f;
+        self.sr = (self.sr & !0xf) | (mode >> 2);
+    }
+
+    /// COP0 is always usable in kernel mode, COP1 and COP3 do not exist.
+    pub fn coprocessor_usable(&self, cop: u32) -> bool {
+        match cop {
+            0 => self.sr & SR_KUC == 0 || self.sr & SR_CU0 != 0,
+            2 => self.sr & SR_CU2 != 0,
+            _ => false,
+        }
+    }
+
+    pub fn set_coprocessor_error(&mut self, cop: u32) {
+        self.cause = (self.cause & !(0x3 << CAUSE_CE_SHIFT)) | (cop << CAUSE_CE_SHIFT);
+    }
+
+    pub fn cache_isolated(&self) -> bool {
+        self.sr & SR_ISC != 0
+    }
+
+    /// Mirrors the interrupt controller output into CAUSE.
+    pub fn set_hardware_interrupt(&mut self, active: bool) {
+        if active {
+            self.cause |= CAUSE_HARDWARE_INTERRUPT;
+        } else {
+            self.cause &= !CAUSE_HARDWARE_INTERRUPT;
+        }
+    }
+
+    /// Whether an unmasked interrupt is pending and interrupts are enabled.
+    pub fn interrupt_pending(&self) -> bool {
+        let pending = (self.cause & self.sr) & 0xff00;
+
+        self.sr & SR_IEC != 0 && pending != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cpu::Cpu,
+        decoded_instruction::{COpType, DecodedInstruction},
+        memory::RAM_CAPACITY_RETAIL,
+    };
+
+    /// Decodes a coprocessor instruction into (class, cop, rt, rd, imm_se,
+    /// command).
+    fn decode_c(word: u32) -> (COpType, u32, u32, u32, u32, u32) {
+        match DecodedInstruction::from(word) {
+            DecodedInstruction::C {
+                op,
+                cop,
+                rt,
+                rd,
+                imm_se,
+                command,
+                ..
+            } => (op, cop, rt, rd, imm_se, command),
+            _ => panic!("{:08x} is not a coprocessor instruction", word),
+        }
+    }
+
+    /// Runs `word` from RAM with the given SR and returns the CPU.
+    fn run_with_sr(word: u32, sr: u32) -> Cpu {
+        let mut cpu = Cpu::new(RAM_CAPACITY_RETAIL);
+
+        cpu.memory.store32(0x80010000, word).unwrap();
+        cpu.pc = 0x80010000;
+        cpu.next_pc = 0x80010004;
+        cpu.cop0.sr = sr;
+        cpu.run_next_instruction(false);
+        cpu
+    }
+
+    #[test]
+    fn exception_in_a_delay_slot_returns_to_the_branch() {
+        let mut cop0 = Cop0::new();
+
+        cop0.enter_exception(Exception::Syscall, 0x80010004, true);
+
+        assert_eq!(cop0.epc, 0x80010000);
+        assert_eq!(cop0.cause & CAUSE_BD, CAUSE_BD);
+        assert_eq!((cop0.cause >> 2) & 0x1f, Exception::Syscall as u32);
+
+        cop0.enter_exception(Exception::Break, 0x80010010, false);
+
+        assert_eq!(cop0.epc, 0x80010010);
+        assert_eq!(cop0.cause & CAUSE_BD, 0);
+        assert_eq!((cop0.cause >> 2) & 0x1f, Exception::Break as u32);
+    }
+
+    #[test]
+    fn exceptions_push_and_rfe_pops_the_mode_stack() {
+        let mut cop0 = Cop0::new();
+
+        // Old: user mode, interrupts enabled. Current: user mode, enabled.
+        cop0.sr = 0b00_01_11;
+        cop0.enter_exception(Exception::Interrupt, 0x80010000, false);
+
+        assert_eq!(cop0.sr & 0x3f, 0b01_11_00);
+        assert_eq!(cop0.sr & (SR_KUC | SR_IEC), 0);
+
+        // The old entry is left in place
+        cop0.return_from_exception();
+
+        assert_eq!(cop0.sr & 0x3f, 0b01_01_11);
+    }
+
+    #[test]
+    fn bev_selects_the_rom_vector() {
+        let mut cop0 = Cop0::new();
+
+        assert_eq!(
+            cop0.enter_exception(Exception::Syscall, 0, false),
+            0xbfc00180
+        );
+
+        cop0.sr &= !SR_BEV;
+
+        assert_eq!(
+            cop0.enter_exception(Exception::Syscall, 0, false),
+            0x80000080
+        );
+    }
+
+    #[test]
+    fn coprocessor_classes_are_decoded_for_every_coprocessor() {
+        use COpType::*;
+
+        let (op, cop, rt, rd, ..) = decode_c(0x40086000); // mfc0 $8, $12
+        assert!(matches!(op, Mfc) && (cop, rt, rd) == (0, 8, 12));
+
+        let (op, cop, rt, rd, ..) = decode_c(0x4842f800); // cfc2 $2, $31
+        assert!(matches!(op, Cfc) && (cop, rt, rd) == (2, 2, 31));
+
+        let (op, cop, rt, rd, ..) = decode_c(0x48820800); // mtc2 $2, $1
+        assert!(matches!(op, Mtc) && (cop, rt, rd) == (2, 2, 1));
+
+        let (op, cop, rt, rd, ..) = decode_c(0x48c2f800); // ctc2 $2, $31
+        assert!(matches!(op, Ctc) && (cop, rt, rd) == (2, 2, 31));
+
+        let (op, cop, _, _, imm_se, _) = decode_c(0x4100fffe); // bc0f -2
+        assert!(matches!(op, Bcf) && cop == 0 && imm_se == 0xfffffffe);
+
+        let (op, cop, _, _, imm_se, _) = decode_c(0x49010003); // bc2t 3
+        assert!(matches!(op, Bct) && cop == 2 && imm_se == 3);
+
+        let (op, cop, .., command) = decode_c(0x42000010); // rfe
+        assert!(matches!(op, Cop) && cop == 0 && command == 0x10);
+
+        let (op, cop, .., command) = decode_c(0x4a180001); // rtps
+        assert!(matches!(op, Cop) && cop == 2 && command == 0x180001);
+
+        let (op, cop, rt, _, imm_se, _) = decode_c(0xc885fff8); // lwc2 $5, -8($4)
+        assert!(matches!(op, Lwc) && (cop, rt, imm_se) == (2, 5, 0xfffffff8));
+
+        let (op, cop, rt, _, imm_se, _) = decode_c(0xe8850008); // swc2 $5, 8($4)
+        assert!(matches!(op, Swc) && (cop, rt, imm_se) == (2, 5, 8));
+
+        assert!(matches!(decode_c(0x44000000), (Mfc, 1, ..))); // mfc1 $0, $0
+        assert!(matches!(decode_c(0x4c000000), (Mfc, 3, ..))); // mfc3 $0, $0
+        assert!(matches!(decode_c(0xc4000000), (Lwc, 1, ..))); // lwc1 $0, 0($0)
+        assert!(matches!(decode_c(0xec000000), (Swc, 3, ..))); // swc3 $0, 0($0)
+    }
+
+    #[test]
+    fn missing_coprocessors_are_unusable() {
+        for &(word, cop) in &[
+            (0x44000000, 1), // mfc1 $0, $0
+            (0x4c000000, 3), // mfc3 $0, $0
+            (0xc4000000, 1), // lwc1 $0, 0($0)
+            (0xec000000, 3), // swc3 $0, 0($0)
+            (0xc0000000, 0), // lwc0 $0, 0($0)
+            (0x48020000, 2), // mfc2 $2, $0 with the GTE disabled
+        ] {
+            let sr = if cop == 2 { SR_CU0 } else { SR_CU0 | SR_CU2 };
+            let cpu = run_with_sr(word, sr);
+
+            assert_eq!(
+                (cpu.cop0.cause >> 2) & 0x1f,
+                Exception::CoprocessorUnusable as u32,
+                "{:08x}",
+                word
+            );
+            assert_eq!(
+                (cpu.cop0.cause >> CAUSE_CE_SHIFT) & 0x3,
+                cop,
+                "{:08x}",
+                word
+            );
+            assert_eq!(cpu.cop0.epc, 0x80010000);
+            assert_eq!(cpu.pc, 0x80000080);
+        }
+    }
+
+    #[test]
+    fn cop0_needs_cu0_in_user_mode() {
+        let cpu = run_with_sr(0x40086000, SR_KUC); // mfc0 $8, $12
+
+        assert_eq!(
+            (cpu.cop0.cause >> 2) & 0x1f,
+            Exception::CoprocessorUnusable as u32
+        );
+        assert_eq!((cpu.cop0.cause >> CAUSE_CE_SHIFT) & 0x3, 0);
+
+        let cpu = run_with_sr(0x40086000, SR_KUC | SR_CU0);
+
+        assert_eq!((cpu.cop0.cause >> 2) & 0x1f, 0);
+        assert_eq!(cpu.pc, 0x80010004);
+    }
+}
@@ -1,43 +1,118 @@
 use crate::{
     bios::Bios,
-    decoded_instruction::{DecodedInstruction, EOpType, FOpType, IOpType, JOpType, ROpType},
+    cop0::{Cop0, Exception},
+    decoded_instruction::{COpType, DecodedInstruction, EOpType, IOpType, JOpType, ROpType},
     generic_error::GenericError,
-    logger::{handle_critical_result, handle_result},
+    gte::Gte,
+    icache::Icache,
+    logger::{handle_critical_result, log_error},
     memory::Memory,
+    memory_region::{cached, AccessWidth},
 };
 
 #[derive(Debug, Clone)]
 pub struct Cpu {
-    pub pc: u32,
+    pub pc: u32,         // Address of the next instruction to fetch
+    pub next_pc: u32,    // Address of the instruction after that one
+    pub current_pc: u32, // Address of the instruction being executed
     pub memory: Memory,
-    pub gpr: [u32; 32], // General Purpose Registers ($0 - $31)
-    pub branch_delay_slot: u32,
-    pub sr: u32,
+    pub gpr: [u32; 32],           // General Purpose Registers ($0 - $31)
+    pub out_gpr: [u32; 32],       // Registers as written by the current instruction
+    pub pending_load: (u32, u32), // Load delay slot (target register, value)
+    pub branch: bool,             // The current instruction is a taken branch or jump
+    pub in_delay_slot: bool,      // The current instruction is in a branch delay slot
+    pub cop0: Cop0,
+    pub gte: Gte,
+    pub hi: u32, // Multiplication / division result (high word or remainder)
+    pub lo: u32, // Multiplication / division result (low word or quotient)
+    pub icache: Icache,
+    pub cycles: u64, // CPU cycles elapsed since reset
 }
 
 impl Cpu {
-    pub fn new() -> Self {
+    pub fn new(ram_capacity: usize) -> Self {
         Self {
             pc: 0xbfc00000,
-            memory: Memory::new(),
+            next_pc: 0xbfc00004,
+            current_pc: 0xbfc00000,
+            memory: Memory::new(ram_capacity),
             gpr: [0; 32],
-            branch_delay_slot: 0x00000000, // nop
-            sr: 0x00000000,
+            out_gpr: [0; 32],
+            pending_load: (0, 0),
+            branch: false,
+            in_delay_slot: false,
+            cop0: Cop0::new(),
+            gte: Gte::new(),
+            hi: 0x00000000,
+            lo: 0x00000000,
+            icache: Icache::new(),
+            cycles: 0,
         }
     }
 
+    /// Runs until the video output reaches the end of a frame.
+    pub fn run_frame(&mut self, print: bool) {
+        while !self.memory.video_timing.frame_ended {
+            self.run_next_instruction(print);
+        }
+
+        self.memory.video_timing.frame_ended = false;
+    }
+
     pub fn run_next_instruction(&mut self, print: bool) {
-        let pc = self.pc;
-        let instruction = self.load32(pc);
+        let start = self.cycles;
 
-        self.pc = pc.wrapping_add(4);
+        self.run_instruction(print);
+        self.memory.tick((self.cycles - start) as u32);
+    }
 
-        self.branch_delay_slot = self.load32(self.pc);
+    fn run_instruction(&mut self, print: bool) {
+        self.current_pc = self.pc;
+        self.in_delay_slot = self.branch;
+        self.branch = false;
 
-        let result = self.decode_and_execute(instruction, print);
+        let instruction = match self.fetch32(self.current_pc) {
+            Some(instruction) => instruction,
+            None => return,
+        };
+
+        // Branches only redirect `next_pc`, so the instruction following
+        // them (the delay slot) is always fetched and executed normally.
+        self.pc = self.next_pc;
+        self.next_pc = self.next_pc.wrapping_add(4);
+
+        self.cop0
+            .set_hardware_interrupt(self.memory.interrupt_controller.pending());
+
+        if self.cop0.interrupt_pending() {
+            self.exception(Exception::Interrupt);
+            return;
+        }
+
+        let result = self.execute(instruction, print);
         handle_critical_result(result, Some("Instruction processing error:"));
     }
 
+    /// Executes a single instruction, retiring the value of the load issued
+    /// by the previous instruction (if any) first. Loads are not visible to
+    /// the instruction immediately after them, and a register written by
+    /// that instruction takes precedence over the pending load. A load into
+    /// the same register discards the pending value altogether.
+    pub fn execute(&mut self, instruction: u32, print: bool) -> Result<(), GenericError> {
+        let (index, value) = self.pending_load;
+        self.pending_load = (0, 0);
+        self.set_reg(index, value);
+
+        let result = self.decode_and_execute(instruction, print);
+
+        if index != 0 && self.pending_load.0 == index {
+            self.out_gpr[index as usize] = self.gpr[index as usize];
+        }
+
+        self.gpr = self.out_gpr;
+        result
+    }
+
     pub fn decode_and_execute(
         &mut self,
         instruction: u32,
@@ -53,132 +128,608 @@ impl Cpu {
                 rd,
                 shamt,
             } => match op {
-                ROpType::Add => self.instruction_error(format!("{:?}", op), instruction, false),
-                ROpType::Addu => self.instruction_error(format!("{:?}", op), instruction, false),
-                ROpType::And => self.instruction_error(format!("{:?}", op), instruction, false),
-                ROpType::Brk => self.instruction_error(format!("{:?}", op), instruction, false),
-                ROpType::Div => self.instruction_error(format!("{:?}", op), instruction, false),
-                ROpType::Jalr => self.instruction_error(format!("{:?}", op), instruction, false),
-                ROpType::Jr => self.instruction_error(format!("{:?}", op), instruction, false),
-                ROpType::Mfhi => self.instruction_error(format!("{:?}", op), instruction, false),
-                ROpType::Mflo => self.instruction_error(format!("{:?}", op), instruction, false),
-                ROpType::Mult => self.instruction_error(format!("{:?}", op), instruction, false),
+                ROpType::Add => self.add(rs, rt, rd, print),
+                ROpType::Addu => self.addu(rs, rt, rd, print),
+                ROpType::And => self.and(rs, rt, rd, print),
+                ROpType::Brk => self.brk(print),
+                ROpType::Div => self.div(rs, rt, print),
+                ROpType::Divu => self.divu(rs, rt, print),
+                ROpType::Jalr => self.jalr(rs, rd, print),
+                ROpType::Jr => self.jr(rs, print),
+                ROpType::Mfhi => self.mfhi(rd, print),
+                ROpType::Mflo => self.mflo(rd, print),
+                ROpType::Mthi => self.mthi(rs, print),
+                ROpType::Mtlo => self.mtlo(rs, print),
+                ROpType::Mult => self.mult(rs, rt, print),
+                ROpType::Multu => self.multu(rs, rt, print),
                 ROpType::Nop => self.nop(print),
-                ROpType::Nor => self.instruction_error(format!("{:?}", op), instruction, false),
+                ROpType::Nor => self.nor(rs, rt, rd, print),
                 ROpType::Or => self.or(rs, rt, rd, print),
                 ROpType::Sll => self.sll(rt, rd, shamt, print),
-                ROpType::Slt => self.instruction_error(format!("{:?}", op), instruction, false),
-                ROpType::Sltu => self.instruction_error(format!("{:?}", op), instruction, false),
+                ROpType::Sllv => self.sllv(rs, rt, rd, print),
+                ROpType::Slt => self.slt(rs, rt, rd, print),
+                ROpType::Sltu => self.sltu(rs, rt, rd, print),
+                ROpType::Sra => self.sra(rt, rd, shamt, print),
+                ROpType::Srav => self.srav(rs, rt, rd, print),
                 ROpType::Srl => self.srl(rt, rd, shamt, print),
-                ROpType::Sub => self.instruction_error(format!("{:?}", op), instruction, false),
-                ROpType::Subu => self.instruction_error(format!("{:?}", op), instruction, false),
-                ROpType::Syscall => self.instruction_error(format!("{:?}", op), instruction, false),
-                ROpType::Xor => self.instruction_error(format!("{:?}", op), instruction, false),
+                ROpType::Srlv => self.srlv(rs, rt, rd, print),
+                ROpType::Sub => self.sub(rs, rt, rd, print),
+                ROpType::Subu => self.subu(rs, rt, rd, print),
+                ROpType::Syscall => self.syscall(print),
+                ROpType::Xor => self.xor(rs, rt, rd, print),
             },
-            DecodedInstruction::I { op, rs, rt, imm } => match op {
-                IOpType::Addi => self.addi(rt, rs, imm, print),
-                IOpType::Addiu => self.addiu(rt, rs, imm, print),
-                IOpType::Andi => self.instruction_error(format!("{:?}", op), instruction, false),
-                IOpType::Beq => self.instruction_error(format!("{:?}", op), instruction, false),
-                IOpType::Blez => self.instruction_error(format!("{:?}", op), instruction, false),
-                IOpType::Bne => self.bne(rs, rt, imm, print),
-                IOpType::Lb => self.instruction_error(format!("{:?}", op), instruction, false),
-                IOpType::Lbu => self.instruction_error(format!("{:?}", op), instruction, false),
-                IOpType::Ldc1 => self.instruction_error(format!("{:?}", op), instruction, false),
-                IOpType::Lh => self.instruction_error(format!("{:?}", op), instruction, false),
-                IOpType::Lhu => self.instruction_error(format!("{:?}", op), instruction, false),
-                IOpType::Lui => self.lui(rt, imm, print),
-                IOpType::Lw => self.instruction_error(format!("{:?}", op), instruction, false),
-                IOpType::Lwc1 => self.instruction_error(format!("{:?}", op), instruction, false),
-                IOpType::Ori => self.ori(rt, rs, imm, print),
-                IOpType::Sb => self.instruction_error(format!("{:?}", op), instruction, false),
-                IOpType::Sh => self.instruction_error(format!("{:?}", op), instruction, false),
-                IOpType::Slti => self.instruction_error(format!("{:?}", op), instruction, false),
-                IOpType::Sltiu => self.instruction_error(format!("{:?}", op), instruction, false),
-                IOpType::Sw => self.sw(rs, rt, imm, print),
+            DecodedInstruction::I {
+                op,
+                rs,
+                rt,
+                imm_se,
+                imm_ze,
+            } => match op {
+                IOpType::Addi => self.addi(rt, rs, imm_se, print),
+                IOpType::Addiu => self.addiu(rt, rs, imm_se, print),
+                IOpType::Andi => self.andi(rt, rs, imm_ze, print),
+                IOpType::Beq => self.beq(rs, rt, imm_se, print),
+                IOpType::Bgez => self.bgez(rs, imm_se, print),
+                IOpType::Bgezal => self.bgezal(rs, imm_se, print),
+                IOpType::Bgtz => self.bgtz(rs, imm_se, print),
+                IOpType::Blez => self.blez(rs, imm_se, print),
+                IOpType::Bltz => self.bltz(rs, imm_se, print),
+                IOpType::Bltzal => self.bltzal(rs, imm_se, print),
+                IOpType::Bne => self.bne(rs, rt, imm_se, print),
+                IOpType::Lb => self.lb(rs, rt, imm_se, print),
+                IOpType::Lbu => self.lbu(rs, rt, imm_se, print),
+                IOpType::Lh => self.lh(rs, rt, imm_se, print),
+                IOpType::Lhu => self.lhu(rs, rt, imm_se, print),
+                IOpType::Lui => self.lui(rt, imm_ze, print),
+                IOpType::Lw => self.lw(rs, rt, imm_se, print),
+                IOpType::Lwl => self.lwl(rs, rt, imm_se, print),
+                IOpType::Lwr => self.lwr(rs, rt, imm_se, print),
+                IOpType::Ori => self.ori(rt, rs, imm_ze, print),
+                IOpType::Sb => self.sb(rs, rt, imm_se, print),
+                IOpType::Sh => self.sh(rs, rt, imm_se, print),
+                IOpType::Slti => self.slti(rt, rs, imm_se, print),
+                IOpType::Sltiu => self.sltiu(rt, rs, imm_se, print),
+                IOpType::Sw => self.sw(rs, rt, imm_se, print),
+                IOpType::Swl => self.swl(rs, rt, imm_se, print),
+                IOpType::Swr => self.swr(rs, rt, imm_se, print),
+                IOpType::Xori => self.xori(rt, rs, imm_ze, print),
             },
             DecodedInstruction::J { op, addr } => match op {
                 JOpType::J => self.j(addr, print),
-                JOpType::Jal => self.instruction_error(format!("{:?}", op), instruction, false),
+                JOpType::Jal => self.jal(addr, print),
             },
-            DecodedInstruction::F { op, rt, rs, rd } => match op {
-                FOpType::Addd => self.instruction_error(format!("{:?}", op), instruction, false),
-                FOpType::Adds => self.instruction_error(format!("{:?}", op), instruction, false),
-                FOpType::Cvtdw => self.instruction_error(format!("{:?}", op), instruction, false),
-                FOpType::Cvtsd => self.instruction_error(format!("{:?}", op), instruction, false),
-                FOpType::Divd => self.instruction_error(format!("{:?}", op), instruction, false),
-                FOpType::Divs => self.instruction_error(format!("{:?}", op), instruction, false),
-                FOpType::Mfc1 => self.instruction_error(format!("{:?}", op), instruction, false),
-                FOpType::Movd => self.instruction_error(format!("{:?}", op), instruction, false),
-                FOpType::Movs => self.instruction_error(format!("{:?}", op), instruction, false),
-                FOpType::Mtc1 => self.instruction_error(format!("{:?}", op), instruction, false),
-                FOpType::Muld => self.instruction_error(format!("{:?}", op), instruction, false),
-                FOpType::Muls => self.instruction_error(format!("{:?}", op), instruction, false),
-                FOpType::Mtc0 => self.mtc0(rt, rs, print),
+            DecodedInstruction::C {
+                op,
+                cop,
+                rs,
+                rt,
+                rd,
+                imm_se,
+                command,
+            } => match (cop, op) {
+                _ if !self.cop0.coprocessor_usable(cop) => self.coprocessor_unusable(cop, print),
+                (0, COpType::Mfc) => self.mfc0(rt, rd, print),
+                (0, COpType::Mtc) => self.mtc0(rt, rd, print),
+                (0, COpType::Cop) if command & 0x3f == 0x10 => self.rfe(print),
+                (0, COpType::Lwc) | (0, COpType::Swc) => self.coprocessor_unusable(cop, print),
+                (2, COpType::Mfc) => self.mfc2(rt, rd, print),
+                (2, COpType::Cfc) => self.cfc2(rt, rd, print),
+                (2, COpType::Mtc) => self.mtc2(rt, rd, print),
+                (2, COpType::Ctc) => self.ctc2(rt, rd, print),
+                (2, COpType::Cop) => self.cop2(command, print),
+                (2, COpType::Lwc) => self.lwc2(rs, rt, imm_se, print),
+                (2, COpType::Swc) => self.swc2(rs, rt, imm_se, print),
+                (_, COpType::Bcf) => self.bcf(cop, imm_se, print),
+                (_, COpType::Bct) => self.bct(cop, imm_se, print),
+                _ => self.reserved_instruction(instruction, print),
             },
             DecodedInstruction::E { op, instruction } => match op {
-                EOpType::Unknown => self.instruction_error(format!("{:?}", op), instruction, true),
+                EOpType::Unknown => self.reserved_instruction(instruction, print),
             },
         }
     }
 
-    pub fn instruction_error(
-        &self,
-        op: String,
-        instruction: u32,
-        unknown: bool,
-    ) -> Result<(), GenericError> {
-        Err(GenericError {
-            message: if unknown {
-                format!(
-                    "UNKNOWN_INSTRUCTION (found 0x{:08x} at 0x{:08x})",
-                    instruction,
-                    self.pc.wrapping_sub(4)
-                )
-            } else {
-                format!(
-                    "UNIMPLEMENTED_INSTRUCTION_{} (0x{:08x} at 0x{:08x})",
-                    op,
-                    instruction,
-                    self.pc.wrapping_sub(4)
-                )
-            },
-        })
+    /// Raises an exception: the instruction at `current_pc` is abandoned and
+    /// execution resumes at the exception handler.
+    pub fn exception(&mut self, cause: Exception) {
+        let handler = self
+            .cop0
+            .enter_exception(cause, self.current_pc, self.in_delay_slot);
+
+        self.pc = handler;
+        self.next_pc = handler.wrapping_add(4);
+        self.branch = false;
+    }
+
+    fn address_error(&mut self, cause: Exception, address: u32) {
+        self.cop0.badvaddr = address;
+        self.exception(cause);
     }
 
-    pub fn load32(&self, address: u32) -> u32 {
-        let result = self.memory.load32(address);
-        handle_critical_result(result, Some("Instruction fetch error:"))
+    fn bus_error(&mut self, cause: Exception, err: GenericError) {
+        log_error(Some("Bus error:"), err);
+        self.exception(cause);
+    }
+
+    pub fn fetch32(&mut self, address: u32) -> Option<u32> {
+        if !address.is_multiple_of(4) {
+            self.address_error(Exception::LoadAddressError, address);
+            return None;
+        }
+
+        if cached(address) && self.memory.cache_control.icache_enable {
+            return self.fetch_cached(address);
+        }
+
+        self.cycles += self.data_cycles(address, AccessWidth::Word, false);
+
+        match self.memory.load32(address) {
+            Ok(word) => Some(word),
+            Err(err) => {
+                self.bus_error(Exception::InstructionBusError, err);
+                None
+            }
+        }
+    }
+
+    /// A hit takes a single cycle, a miss refills the line from the missed
+    /// word to its end.
+    fn fetch_cached(&mut self, address: u32) -> Option<u32> {
+        if let Some(word) = self.icache.lookup(address) {
+            self.cycles += 1;
+            return Some(word);
+        }
+
+        let mut words = Vec::with_capacity(4);
+
+        for word_address in (address..(address | 0xf)).step_by(4) {
+            match self.memory.load32(word_address) {
+                Ok(word) => words.push(word),
+                Err(err) => {
+                    self.bus_error(Exception::InstructionBusError, err);
+                    return None;
+                }
+            }
+        }
+
+        self.cycles += self.data_cycles(address, AccessWidth::Word, false) + words.len() as u64 - 1;
+        self.icache.fill(address, &words);
+
+        Some(words[0])
+    }
+
+    pub fn load32(&mut self, address: u32) -> Option<u32> {
+        if !address.is_multiple_of(4) {
+            self.address_error(Exception::LoadAddressError, address);
+            return None;
+        }
+
+        self.cycles += self.data_cycles(address, AccessWidth::Word, false);
+
+        match self.memory.load32(address) {
+            Ok(word) => Some(word),
+            Err(err) => {
+                self.bus_error(Exception::DataBusError, err);
+                None
+            }
+        }
+    }
+
+    pub fn load16(&mut self, address: u32) -> Option<u16> {
+        if !address.is_multiple_of(2) {
+            self.address_error(Exception::LoadAddressError, address);
+            return None;
+        }
+
+        self.cycles += self.data_cycles(address, AccessWidth::Halfword, false);
+
+        match self.memory.load16(address) {
+            Ok(halfword) => Some(halfword),
+            Err(err) => {
+                self.bus_error(Exception::DataBusError, err);
+                None
+            }
+        }
+    }
+
+    pub fn load8(&mut self, address: u32) -> Option<u8> {
+        self.cycles += self.data_cycles(address, AccessWidth::Byte, false);
+
+        match self.memory.load8(address) {
+            Ok(byte) => Some(byte),
+            Err(err) => {
+                self.bus_error(Exception::DataBusError, err);
+                None
+            }
+        }
     }
 
     pub fn store32(&mut self, address: u32, word: u32) {
-        let message = Some("Memory write soft error:");
-        let result = if self.sr & 0x10000 != 0 {
-            Err(GenericError {
-                message: format!("IGNORE_WRITE_ON_ISOLATED_CACHE (at 0x{:x})", self.pc - 4),
-            })
-        } else {
-            Ok(())
-        };
+        if !address.is_multiple_of(4) {
+            self.address_error(Exception::StoreAddressError, address);
+            return;
+        }
+
+        self.cycles += self.data_cycles(address, AccessWidth::Word, true);
+
+        if self.store_isolated(address, word) {
+            return;
+        }
+
+        if let Err(err) = self.memory.store32(address, word) {
+            self.bus_error(Exception::DataBusError, err);
+        }
+    }
+
+    /// Stores the bytes of `word` selected by `mask` (`swl` / `swr`) without
+    /// reading the rest of the word back.
+    pub fn store32_masked(&mut self, address: u32, word: u32, mask: u32) {
+        self.cycles += self.data_cycles(address, AccessWidth::Word, true);
+
+        if self.store_isolated(address, word) {
+            return;
+        }
+
+        if let Err(err) = self.memory.store32_masked(address, word, mask) {
+            self.bus_error(Exception::DataBusError, err);
+        }
+    }
+
+    pub fn store16(&mut self, address: u32, halfword: u16) {
+        if !address.is_multiple_of(2) {
+            self.address_error(Exception::StoreAddressError, address);
+            return;
+        }
+
+        self.cycles += self.data_cycles(address, AccessWidth::Halfword, true);
+
+        if self.store_isolated(address, halfword as u32) {
+            return;
+        }
+
+        if let Err(err) = self.memory.store16(address, halfword) {
+            self.bus_error(Exception::DataBusError, err);
+        }
+    }
+
+    pub fn store8(&mut self, address: u32, byte: u8) {
+        self.cycles += self.data_cycles(address, AccessWidth::Byte, true);
+
+        if self.store_isolated(address, byte as u32) {
+            return;
+        }
 
-        if result.is_err() {
-            handle_result(result, message);
+        if let Err(err) = self.memory.store8(address, byte) {
+            self.bus_error(Exception::DataBusError, err);
+        }
+    }
+
+    fn data_cycles(&self, address: u32, width: AccessWidth, write: bool) -> u64 {
+        self.memory.access_cycles(address, width, write) as u64
+    }
+
+    /// While the cache is isolated, stores go to the i-cache instead of
+    /// memory. The BIOS relies on this to flush it.
+    fn store_isolated(&mut self, address: u32, value: u32) -> bool {
+        if !self.cop0.cache_isolated() {
+            return false;
+        }
+
+        if self.memory.cache_control.tag_test {
+            self.icache.invalidate(address);
         } else {
-            let result = self.memory.store32(address, word);
-            handle_critical_result(result, message)
+            self.icache.store(address, value);
         }
+
+        true
     }
 
     pub fn load_bios(&mut self, bios: Bios) {
         self.memory.load_bios(bios);
     }
 
+    /// Redirects execution to `target` after the branch delay slot.
+    pub fn branch(&mut self, target: u32) {
+        self.next_pc = target;
+        self.branch = true;
+    }
+
     pub fn reg(&self, index: u32) -> u32 {
         self.gpr[index as usize]
     }
 
     pub fn set_reg(&mut self, index: u32, value: u32) {
-        self.gpr[index as usize] = value;
-        self.gpr[0] = 0; // $0 is always zero
+        self.out_gpr[index as usize] = value;
+        self.out_gpr[0] = 0; // $0 is always zero
+    }
+
+    /// Schedules a register write for after the next instruction (load delay
+    /// slot). A second load into the same register issued in the delay slot
+    /// replaces this one.
+    pub fn delayed_load(&mut self, index: u32, value: u32) {
+        self.pending_load = (index, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::RAM_CAPACITY_RETAIL;
+    use std::time::Instant;
+
+    const PROGRAM_ADDRESS: u32 = 0x80010000;
+    const DATA_ADDRESS: u32 = 0x80020000;
+
+    /// CPU about to run `program` from RAM.
+    fn load_program(program: &[u32]) -> Cpu {
+        let mut cpu = Cpu::new(RAM_CAPACITY_RETAIL);
+
+        for (index, word) in program.iter().enumerate() {
+            cpu.memory
+                .store32(PROGRAM_ADDRESS + index as u32 * 4, *word)
+                .unwrap();
+        }
+
+        cpu.pc = PROGRAM_ADDRESS;
+        cpu.next_pc = PROGRAM_ADDRESS + 4;
+        cpu
+    }
+
+    fn set_regs(cpu: &mut Cpu, registers: &[(u32, u32)]) {
+        for &(index, value) in registers {
+            cpu.set_reg(index, value);
+        }
+
+        cpu.gpr = cpu.out_gpr;
+    }
+
+    fn run(cpu: &mut Cpu, instructions: usize) {
+        for _ in 0..instructions {
+            cpu.run_next_instruction(false);
+        }
+    }
+
+    /// Cycles taken by the next instruction, fetch included.
+    fn cycles_of_next(cpu: &mut Cpu) -> u64 {
+        let start = cpu.cycles;
+
+        cpu.run_next_instruction(false);
+        cpu.cycles - start
+    }
+
+    #[test]
+    fn load_is_not_visible_in_its_delay_slot() {
+        let mut cpu = load_program(&[
+            0x8d0a0000, // lw $10, 0($8)
+            0x01405821, // addu $11, $10, $0
+            0x01406021, // addu $12, $10, $0
+        ]);
+
+        set_regs(&mut cpu, &[(8, DATA_ADDRESS), (10, 0xdead)]);
+        cpu.memory.store32(DATA_ADDRESS, 0x1234).unwrap();
+        run(&mut cpu, 3);
+
+        assert_eq!(cpu.reg(11), 0xdead);
+        assert_eq!(cpu.reg(12), 0x1234);
+    }
+
+    #[test]
+    fn back_to_back_loads_drop_the_first_value() {
+        let mut cpu = load_program(&[
+            0x8d0d0000, // lw $13, 0($8)
+            0x8d0d0004, // lw $13, 4($8)
+            0x01a07021, // addu $14, $13, $0
+            0x01a07821, // addu $15, $13, $0
+        ]);
+
+        set_regs(&mut cpu, &[(8, DATA_ADDRESS), (13, 5)]);
+        cpu.memory.store32(DATA_ADDRESS, 0x1111).unwrap();
+        cpu.memory.store32(DATA_ADDRESS + 4, 0x2222).unwrap();
+        run(&mut cpu, 4);
+
+        assert_eq!(cpu.reg(14), 5);
+        assert_eq!(cpu.reg(15), 0x2222);
+    }
+
+    #[test]
+    fn lwl_lwr_merge_without_waiting_for_the_delay() {
+        let mut cpu = load_program(&[
+            0x89100004, // lwl $16, 4($8)
+            0x99100001, // lwr $16, 1($8)
+            0x02008821, // addu $17, $16, $0
+            0x02009021, // addu $18, $16, $0
+        ]);
+
+        set_regs(&mut cpu, &[(8, DATA_ADDRESS), (16, 0xffffffff)]);
+        cpu.memory.store32(DATA_ADDRESS, 0x44332211).unwrap();
+        cpu.memory.store32(DATA_ADDRESS + 4, 0x88776655).unwrap();
+        run(&mut cpu, 4);
+
+        assert_eq!(cpu.reg(17), 0xffffffff);
+        assert_eq!(cpu.reg(18), 0x55443322);
+    }
+
+    #[test]
+    fn delay_slot_runs_before_a_taken_branch() {
+        let mut cpu = load_program(&[
+            0x10000002, // beq $0, $0, +2
+            0x34090001, // ori $9, $0, 1
+            0x340a0001, // ori $10, $0, 1
+            0x340b0001, // ori $11, $0, 1
+        ]);
+
+        run(&mut cpu, 3);
+
+        assert_eq!((cpu.reg(9), cpu.reg(10), cpu.reg(11)), (1, 0, 1));
+        assert_eq!(cpu.pc, PROGRAM_ADDRESS + 16);
+    }
+
+    #[test]
+    fn link_skips_the_delay_slot() {
+        let mut cpu = load_program(&[
+            0x0c004004, // jal 0x80010010
+            0x00000000, // nop
+        ]);
+
+        run(&mut cpu, 2);
+
+        assert_eq!(cpu.reg(31), PROGRAM_ADDRESS + 8);
+        assert_eq!(cpu.pc, PROGRAM_ADDRESS + 0x10);
+    }
+
+    #[test]
+    fn branch_in_a_delay_slot_runs_one_instruction_of_the_first_target() {
+        let mut program = [0; 9];
+
+        program[0] = 0x08004004; // j 0x80010010
+        program[1] = 0x08004008; // j 0x80010020
+        program[4] = 0x34090001; // ori $9, $0, 1
+        program[5] = 0x340a0001; // ori $10, $0, 1
+        program[8] = 0x340b0001; // ori $11, $0, 1
+
+        let mut cpu = load_program(&program);
+
+        run(&mut cpu, 3);
+
+        assert_eq!(cpu.current_pc, PROGRAM_ADDRESS + 0x10);
+        assert!(cpu.in_delay_slot);
+
+        run(&mut cpu, 1);
+
+        assert_eq!(cpu.current_pc, PROGRAM_ADDRESS + 0x20);
+        assert_eq!((cpu.reg(9), cpu.reg(10), cpu.reg(11)), (1, 0, 1));
+    }
+
+    #[test]
+    fn bne_loops_back_while_registers_differ() {
+        let mut cpu = load_program(&[
+            0x24030003, // addiu $3, $0, 3
+            0x2463ffff, // loop: addiu $3, $3, -1
+            0x1460fffe, // bne $3, $0, loop
+            0x24840001, // addiu $4, $4, 1
+        ]);
+
+        run(&mut cpu, 10);
+
+        assert_eq!((cpu.reg(3), cpu.reg(4)), (0, 3));
+        assert_eq!(cpu.pc, PROGRAM_ADDRESS + 16);
+    }
+
+    #[test]
+    fn exception_in_a_delay_slot_reports_the_branch() {
+        let mut cpu = load_program(&[
+            0x08004004, // j 0x80010010
+            0x0000000c, // syscall
+        ]);
+
+        run(&mut cpu, 2);
+
+        assert_eq!(cpu.cop0.epc, PROGRAM_ADDRESS);
+        assert_ne!(cpu.cop0.cause & (1 << 31), 0);
+        assert_eq!(cpu.pc, 0xbfc00180);
+    }
+
+    #[test]
+    fn swl_swr_only_write_their_bytes() {
+        let mut cpu = load_program(&[
+            0xa9090001, // swl $9, 1($8)
+            0xb9090006, // swr $9, 6($8)
+        ]);
+
+        set_regs(&mut cpu, &[(8, DATA_ADDRESS), (9, 0xaabbccdd)]);
+        cpu.memory.store32(DATA_ADDRESS, 0x11223344).unwrap();
+        cpu.memory.store32(DATA_ADDRESS + 4, 0x55667788).unwrap();
+        run(&mut cpu, 2);
+
+        assert_eq!(cpu.memory.load32(DATA_ADDRESS).unwrap(), 0x1122aabb);
+        assert_eq!(cpu.memory.load32(DATA_ADDRESS + 4).unwrap(), 0xccdd7788);
+    }
+
+    #[test]
+    fn data_accesses_cost_the_cycles_of_their_region() {
+        let mut cpu = load_program(&[
+            0x8d090000, // lw $9, 0($8)
+            0x8d490000, // lw $9, 0($10)
+            0x8d690000, // lw $9, 0($11)
+            0x8d690000, // lw $9, 0($11)
+        ]);
+
+        set_regs(
+            &mut cpu,
+            &[(8, DATA_ADDRESS), (10, 0x1f800000), (11, 0xbfc00000)],
+        );
+        cpu.load_bios(Bios {
+            data: vec![0; 512 * 1024],
+        });
+
+        // The i-cache is off, every fetch is a RAM read
+        assert_eq!(cycles_of_next(&mut cpu), 5 + 5);
+        assert_eq!(cycles_of_next(&mut cpu), 5 + 1);
+
+        // Boot BIOS ROM timing: 8-bit bus, four transfers
+        assert_eq!(cycles_of_next(&mut cpu), 5 + 25);
+
+        // 16-bit bus, read delay of 8, no COM delays: two transfers
+        cpu.memory.memlcontrol.store_32(0x10, 0x00131070);
+
+        assert_eq!(cycles_of_next(&mut cpu), 5 + 19);
+    }
+
+    #[test]
+    fn icache_hits_take_a_cycle_and_misses_refill_the_rest_of_the_line() {
+        let mut cpu = load_program(&[0; 8]);
+
+        cpu.memory.store32(0xfffe0130, 1 << 11).unwrap();
+        cpu.pc = PROGRAM_ADDRESS + 8;
+        cpu.next_pc = PROGRAM_ADDRESS + 12;
+
+        // Words 2 and 3, then a whole line
+        assert_eq!(cycles_of_next(&mut cpu), 5 + 1);
+        assert_eq!(cycles_of_next(&mut cpu), 1);
+        assert_eq!(cycles_of_next(&mut cpu), 5 + 3);
+        assert_eq!(cycles_of_next(&mut cpu), 1);
+
+        // Words 0 and 1 of the first line were never filled
+        cpu.pc = PROGRAM_ADDRESS;
+        cpu.next_pc = PROGRAM_ADDRESS + 4;
+
+        assert_eq!(cycles_of_next(&mut cpu), 5 + 3);
+        assert_eq!(cycles_of_next(&mut cpu), 1);
+        assert_eq!(cycles_of_next(&mut cpu), 1);
+    }
+
+    /// Tight RAM loop doing a word and a byte round trip per iteration.
+    /// Run with `cargo test --release -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn benchmark_instructions_per_second() {
+        let program = [
+            0x3c088002, // lui $8, 0x8002
+            0x3c0b1f80, // lui $11, 0x1f80
+            0x8d090000, // loop: lw $9, 0($8)
+            0x25290001, // addiu $9, $9, 1
+            0xad090000, // sw $9, 0($8)
+            0x916a0000, // lbu $10, 0($11)
+            0xa16a0001, // sb $10, 1($11)
+            0x08004002, // j loop
+            0x00000000, // nop
+        ];
+        let mut cpu = load_program(&program);
+
+        cpu.memory.store32(0xfffe0130, 0x0001e988).unwrap(); // Scratchpad and i-cache enabled
+
+        let instructions = 20_000_000;
+        let start = Instant::now();
+
+        for _ in 0..instructions {
+            cpu.run_next_instruction(false);
+        }
+
+        let elapsed = start.elapsed().as_secs_f64();
+        println!("{:.0} instructions/s", instructions as f64 / elapsed);
+        assert_eq!(
+            cpu.memory.load32(0x80020000).unwrap(),
+            (instructions - 5) / 7 + 1
+        );
     }
 }
@@ -1,4 +1,4 @@
-use crate::{cpu::Cpu, generic_error::GenericError};
+use crate::{cop0::Exception, cpu::Cpu, generic_error::GenericError};
 
 impl Cpu {
     pub fn lui(&mut self, rt: u32, imm: u32, print: bool) -> Result<(), GenericError> {
@@ -29,7 +29,7 @@ impl Cpu {
         self.store32(target, value);
 
         if print {
-            println!("sw ${}, 0x{:x}(${})", rt, imm, rs);
+            println!("sw ${}, {}(${})", rt, imm as i32, rs);
         }
 
         Ok(())
@@ -46,7 +46,7 @@ impl Cpu {
         self.set_reg(rt, self.reg(rs).wrapping_add(imm));
 
         if print {
-            println!("addiu ${}, ${}, 0x{:x}", rt, rs, imm);
+            println!("addiu ${}, ${}, {}", rt, rs, imm as i32);
         }
 
         Ok(())
@@ -77,15 +77,9 @@ impl Cpu {
             println!("j 0x{:x}", addr);
         }
 
-        // Execute instruction at the branch delay slot
-        let result = self.decode_and_execute(self.branch_delay_slot, print);
-
-        if result.is_err() {
-            return result;
-        } else {
-            self.pc = (self.pc & 0xf0000000) | (addr << 2);
-            Ok(())
-        }
+        // The target keeps the upper bits of the delay slot address
+        self.branch((self.pc & 0xf0000000) | (addr << 2));
+        Ok(())
     }
 
     pub fn or(&mut self, rs: u32, rt: u32, rd: u32, print: bool) -> Result<(), GenericError> {
@@ -98,62 +92,821 @@ impl Cpu {
         Ok(())
     }
 
-    pub fn mtc0(&mut self, rt: u32, rs: u32, print: bool) -> Result<(), GenericError> {
-        match rs {
-            12 => {
-                self.sr = self.reg(rt);
+    pub fn mtc0(&mut self, rt: u32, rd: u32, print: bool) -> Result<(), GenericError> {
+        self.cop0.write(rd, self.reg(rt));
+
+        if print {
+            println!("mtc0 ${}, ${}", rt, rd);
+        }
+
+        Ok(())
+    }
+
+    pub fn mfc0(&mut self, rt: u32, rd: u32, print: bool) -> Result<(), GenericError> {
+        match self.cop0.read(rd) {
+            Some(value) => self.delayed_load(rt, value),
+            None => self.exception(Exception::ReservedInstruction),
+        }
+
+        if print {
+            println!("mfc0 ${}, ${}", rt, rd);
+        }
+
+        Ok(())
+    }
+
+    pub fn rfe(&mut self, print: bool) -> Result<(), GenericError> {
+        self.cop0.return_from_exception();
 
-                if print {
-                    println!("mtc0 ${}, ${}", rt, rs);
-                }
+        if print {
+            println!("rfe");
+        }
 
-                Ok(())
-            }
-            _ => Err(GenericError {
-                message: format!("UNHANDLED_COP0_REGISTER (${})", rs),
-            }),
+        Ok(())
+    }
+
+    pub fn syscall(&mut self, print: bool) -> Result<(), GenericError> {
+        self.exception(Exception::Syscall);
+
+        if print {
+            println!("syscall");
         }
+
+        Ok(())
     }
 
-    fn relative_branch(&mut self, offset: u32, print: bool) -> Result<(), GenericError> {
-        let result = self.decode_and_execute(self.branch_delay_slot, print);
+    pub fn brk(&mut self, print: bool) -> Result<(), GenericError> {
+        self.exception(Exception::Break);
 
-        if result.is_err() {
-            return result;
+        if print {
+            println!("break");
         }
 
-        self.pc = self.pc.wrapping_add(offset << 2);
         Ok(())
     }
 
+    pub fn coprocessor_unusable(&mut self, cop: u32, print: bool) -> Result<(), GenericError> {
+        self.cop0.set_coprocessor_error(cop);
+        self.exception(Exception::CoprocessorUnusable);
+
+        if print {
+            println!("cop{} (unusable)", cop);
+        }
+
+        Ok(())
+    }
+
+    /// Neither COP0 nor the GTE drive the coprocessor condition input, so
+    /// BCzF always branches and BCzT never does.
+    pub fn bcf(&mut self, cop: u32, imm: u32, print: bool) -> Result<(), GenericError> {
+        if print {
+            println!("bc{}f {}", cop, imm as i32);
+        }
+
+        self.relative_branch(imm);
+
+        Ok(())
+    }
+
+    pub fn bct(&mut self, cop: u32, imm: u32, print: bool) -> Result<(), GenericError> {
+        if print {
+            println!("bc{}t {}", cop, imm as i32);
+        }
+
+        Ok(())
+    }
+
+    pub fn reserved_instruction(
+        &mut self,
+        instruction: u32,
+        print: bool,
+    ) -> Result<(), GenericError> {
+        self.exception(Exception::ReservedInstruction);
+
+        if print {
+            println!("reserved 0x{:08x}", instruction);
+        }
+
+        Ok(())
+    }
+
+    /// Branches relative to the address of the delay slot.
+    fn relative_branch(&mut self, offset: u32) {
+        self.branch(self.pc.wrapping_add(offset << 2));
+    }
+
     pub fn bne(&mut self, rs: u32, rt: u32, imm: u32, print: bool) -> Result<(), GenericError> {
         if print {
-            println!("bne ${}, ${}, 0x{:x}", rs, rt, imm)
+            println!("bne ${}, ${}, {}", rs, rt, imm as i32)
         }
 
-        if self.reg(rs) == self.reg(rt) {
-            let result = self.relative_branch(imm, print);
-            if result.is_err() {
-                return result;
-            }
+        if self.reg(rs) != self.reg(rt) {
+            self.relative_branch(imm);
         }
 
         Ok(())
     }
 
     pub fn addi(&mut self, rt: u32, rs: u32, imm: u32, print: bool) -> Result<(), GenericError> {
-        if let Some(sum) = self.reg(rs).checked_add(imm) {
-            self.set_reg(rt, sum);
+        match (self.reg(rs) as i32).checked_add(imm as i32) {
+            Some(sum) => self.set_reg(rt, sum as u32),
+            None => self.exception(Exception::Overflow),
+        }
+
+        if print {
+            println!("addi ${}, ${}, {}", rt, rs, imm as i32);
+        }
+
+        Ok(())
+    }
+
+    pub fn add(&mut self, rs: u32, rt: u32, rd: u32, print: bool) -> Result<(), GenericError> {
+        let a = self.reg(rs) as i32;
+        let b = self.reg(rt) as i32;
+
+        match a.checked_add(b) {
+            Some(sum) => self.set_reg(rd, sum as u32),
+            None => self.exception(Exception::Overflow),
+        }
+
+        if print {
+            println!("add ${}, ${}, ${}", rd, rs, rt);
+        }
+
+        Ok(())
+    }
+
+    pub fn addu(&mut self, rs: u32, rt: u32, rd: u32, print: bool) -> Result<(), GenericError> {
+        self.set_reg(rd, self.reg(rs).wrapping_add(self.reg(rt)));
+
+        if print {
+            println!("addu ${}, ${}, ${}", rd, rs, rt);
+        }
+
+        Ok(())
+    }
 
-            if print {
-                println!("addi ${}, ${}, 0x{:x}", rt, rs, imm);
-            }
+    pub fn sub(&mut self, rs: u32, rt: u32, rd: u32, print: bool) -> Result<(), GenericError> {
+        let a = self.reg(rs) as i32;
+        let b = self.reg(rt) as i32;
+
+        match a.checked_sub(b) {
+            Some(difference) => self.set_reg(rd, difference as u32),
+            None => self.exception(Exception::Overflow),
+        }
+
+        if print {
+            println!("sub ${}, ${}, ${}", rd, rs, rt);
+        }
+
+        Ok(())
+    }
+
+    pub fn subu(&mut self, rs: u32, rt: u32, rd: u32, print: bool) -> Result<(), GenericError> {
+        self.set_reg(rd, self.reg(rs).wrapping_sub(self.reg(rt)));
+
+        if print {
+            println!("subu ${}, ${}, ${}", rd, rs, rt);
+        }
+
+        Ok(())
+    }
+
+    pub fn and(&mut self, rs: u32, rt: u32, rd: u32, print: bool) -> Result<(), GenericError> {
+        self.set_reg(rd, self.reg(rs) & self.reg(rt));
+
+        if print {
+            println!("and ${}, ${}, ${}", rd, rs, rt);
+        }
+
+        Ok(())
+    }
+
+    pub fn xor(&mut self, rs: u32, rt: u32, rd: u32, print: bool) -> Result<(), GenericError> {
+        self.set_reg(rd, self.reg(rs) ^ self.reg(rt));
+
+        if print {
+            println!("xor ${}, ${}, ${}", rd, rs, rt);
+        }
+
+        Ok(())
+    }
+
+    pub fn nor(&mut self, rs: u32, rt: u32, rd: u32, print: bool) -> Result<(), GenericError> {
+        self.set_reg(rd, !(self.reg(rs) | self.reg(rt)));
+
+        if print {
+            println!("nor ${}, ${}, ${}", rd, rs, rt);
+        }
+
+        Ok(())
+    }
 
-            Ok(())
+    pub fn slt(&mut self, rs: u32, rt: u32, rd: u32, print: bool) -> Result<(), GenericError> {
+        let less = (self.reg(rs) as i32) < (self.reg(rt) as i32);
+        self.set_reg(rd, less as u32);
+
+        if print {
+            println!("slt ${}, ${}, ${}", rd, rs, rt);
+        }
+
+        Ok(())
+    }
+
+    pub fn sltu(&mut self, rs: u32, rt: u32, rd: u32, print: bool) -> Result<(), GenericError> {
+        let less = self.reg(rs) < self.reg(rt);
+        self.set_reg(rd, less as u32);
+
+        if print {
+            println!("sltu ${}, ${}, ${}", rd, rs, rt);
+        }
+
+        Ok(())
+    }
+
+    pub fn sra(&mut self, rt: u32, rd: u32, shamt: u32, print: bool) -> Result<(), GenericError> {
+        self.set_reg(rd, ((self.reg(rt) as i32) >> shamt) as u32);
+
+        if print {
+            println!("sra ${}, ${}, 0x{:x}", rd, rt, shamt);
+        }
+
+        Ok(())
+    }
+
+    pub fn sllv(&mut self, rs: u32, rt: u32, rd: u32, print: bool) -> Result<(), GenericError> {
+        self.set_reg(rd, self.reg(rt) << (self.reg(rs) & 0x1f));
+
+        if print {
+            println!("sllv ${}, ${}, ${}", rd, rt, rs);
+        }
+
+        Ok(())
+    }
+
+    pub fn srlv(&mut self, rs: u32, rt: u32, rd: u32, print: bool) -> Result<(), GenericError> {
+        self.set_reg(rd, self.reg(rt) >> (self.reg(rs) & 0x1f));
+
+        if print {
+            println!("srlv ${}, ${}, ${}", rd, rt, rs);
+        }
+
+        Ok(())
+    }
+
+    pub fn srav(&mut self, rs: u32, rt: u32, rd: u32, print: bool) -> Result<(), GenericError> {
+        self.set_reg(rd, ((self.reg(rt) as i32) >> (self.reg(rs) & 0x1f)) as u32);
+
+        if print {
+            println!("srav ${}, ${}, ${}", rd, rt, rs);
+        }
+
+        Ok(())
+    }
+
+    pub fn mult(&mut self, rs: u32, rt: u32, print: bool) -> Result<(), GenericError> {
+        let a = self.reg(rs) as i32 as i64;
+        let b = self.reg(rt) as i32 as i64;
+        let product = (a * b) as u64;
+
+        self.hi = (product >> 32) as u32;
+        self.lo = product as u32;
+
+        if print {
+            println!("mult ${}, ${}", rs, rt);
+        }
+
+        Ok(())
+    }
+
+    pub fn multu(&mut self, rs: u32, rt: u32, print: bool) -> Result<(), GenericError> {
+        let a = self.reg(rs) as u64;
+        let b = self.reg(rt) as u64;
+        let product = a * b;
+
+        self.hi = (product >> 32) as u32;
+        self.lo = product as u32;
+
+        if print {
+            println!("multu ${}, ${}", rs, rt);
+        }
+
+        Ok(())
+    }
+
+    pub fn div(&mut self, rs: u32, rt: u32, print: bool) -> Result<(), GenericError> {
+        let n = self.reg(rs) as i32;
+        let d = self.reg(rt) as i32;
+
+        if d == 0 {
+            // Division by zero does not trap, it produces garbage results
+            self.hi = n as u32;
+            self.lo = if n >= 0 { 0xffffffff } else { 1 };
+        } else if n == i32::MIN && d == -1 {
+            // The quotient cannot be represented in 32 bits
+            self.hi = 0;
+            self.lo = n as u32;
         } else {
-            return Err(GenericError {
-                message: format!("ADDI_ARITHMETIC_OVERFLOW"),
-            });
+            self.hi = (n % d) as u32;
+            self.lo = (n / d) as u32;
         }
+
+        if print {
+            println!("div ${}, ${}", rs, rt);
+        }
+
+        Ok(())
+    }
+
+    pub fn divu(&mut self, rs: u32, rt: u32, print: bool) -> Result<(), GenericError> {
+        let n = self.reg(rs);
+        let d = self.reg(rt);
+
+        if d == 0 {
+            self.hi = n;
+            self.lo = 0xffffffff;
+        } else {
+            self.hi = n % d;
+            self.lo = n / d;
+        }
+
+        if print {
+            println!("divu ${}, ${}", rs, rt);
+        }
+
+        Ok(())
+    }
+
+    pub fn mfhi(&mut self, rd: u32, print: bool) -> Result<(), GenericError> {
+        self.set_reg(rd, self.hi);
+
+        if print {
+            println!("mfhi ${}", rd);
+        }
+
+        Ok(())
+    }
+
+    pub fn mflo(&mut self, rd: u32, print: bool) -> Result<(), GenericError> {
+        self.set_reg(rd, self.lo);
+
+        if print {
+            println!("mflo ${}", rd);
+        }
+
+        Ok(())
+    }
+
+    pub fn mthi(&mut self, rs: u32, print: bool) -> Result<(), GenericError> {
+        self.hi = self.reg(rs);
+
+        if print {
+            println!("mthi ${}", rs);
+        }
+
+        Ok(())
+    }
+
+    pub fn mtlo(&mut self, rs: u32, print: bool) -> Result<(), GenericError> {
+        self.lo = self.reg(rs);
+
+        if print {
+            println!("mtlo ${}", rs);
+        }
+
+        Ok(())
+    }
+
+    pub fn jal(&mut self, addr: u32, print: bool) -> Result<(), GenericError> {
+        // Return address skips over the branch delay slot
+        self.set_reg(31, self.next_pc);
+
+        if print {
+            println!("jal 0x{:x}", addr);
+        }
+
+        self.branch((self.pc & 0xf0000000) | (addr << 2));
+        Ok(())
+    }
+
+    pub fn jr(&mut self, rs: u32, print: bool) -> Result<(), GenericError> {
+        let target = self.reg(rs);
+
+        if print {
+            println!("jr ${}", rs);
+        }
+
+        self.branch(target);
+        Ok(())
+    }
+
+    pub fn jalr(&mut self, rs: u32, rd: u32, print: bool) -> Result<(), GenericError> {
+        let target = self.reg(rs);
+        self.set_reg(rd, self.next_pc);
+
+        if print {
+            println!("jalr ${}, ${}", rd, rs);
+        }
+
+        self.branch(target);
+        Ok(())
+    }
+
+    pub fn beq(&mut self, rs: u32, rt: u32, imm: u32, print: bool) -> Result<(), GenericError> {
+        if print {
+            println!("beq ${}, ${}, {}", rs, rt, imm as i32)
+        }
+
+        if self.reg(rs) == self.reg(rt) {
+            self.relative_branch(imm);
+        }
+
+        Ok(())
+    }
+
+    pub fn blez(&mut self, rs: u32, imm: u32, print: bool) -> Result<(), GenericError> {
+        if print {
+            println!("blez ${}, {}", rs, imm as i32)
+        }
+
+        if self.reg(rs) as i32 <= 0 {
+            self.relative_branch(imm);
+        }
+
+        Ok(())
+    }
+
+    pub fn bgtz(&mut self, rs: u32, imm: u32, print: bool) -> Result<(), GenericError> {
+        if print {
+            println!("bgtz ${}, {}", rs, imm as i32)
+        }
+
+        if self.reg(rs) as i32 > 0 {
+            self.relative_branch(imm);
+        }
+
+        Ok(())
+    }
+
+    pub fn bltz(&mut self, rs: u32, imm: u32, print: bool) -> Result<(), GenericError> {
+        if print {
+            println!("bltz ${}, {}", rs, imm as i32)
+        }
+
+        if (self.reg(rs) as i32) < 0 {
+            self.relative_branch(imm);
+        }
+
+        Ok(())
+    }
+
+    pub fn bgez(&mut self, rs: u32, imm: u32, print: bool) -> Result<(), GenericError> {
+        if print {
+            println!("bgez ${}, {}", rs, imm as i32)
+        }
+
+        if self.reg(rs) as i32 >= 0 {
+            self.relative_branch(imm);
+        }
+
+        Ok(())
+    }
+
+    pub fn bltzal(&mut self, rs: u32, imm: u32, print: bool) -> Result<(), GenericError> {
+        if print {
+            println!("bltzal ${}, {}", rs, imm as i32)
+        }
+
+        // The condition is evaluated before $ra is written, and the link
+        // happens whether or not the branch is taken.
+        let taken = (self.reg(rs) as i32) < 0;
+        self.set_reg(31, self.next_pc);
+
+        if taken {
+            self.relative_branch(imm);
+        }
+
+        Ok(())
+    }
+
+    pub fn bgezal(&mut self, rs: u32, imm: u32, print: bool) -> Result<(), GenericError> {
+        if print {
+            println!("bgezal ${}, {}", rs, imm as i32)
+        }
+
+        let taken = self.reg(rs) as i32 >= 0;
+        self.set_reg(31, self.next_pc);
+
+        if taken {
+            self.relative_branch(imm);
+        }
+
+        Ok(())
+    }
+
+    pub fn andi(&mut self, rt: u32, rs: u32, imm: u32, print: bool) -> Result<(), GenericError> {
+        self.set_reg(rt, self.reg(rs) & imm);
+
+        if print {
+            println!("andi ${}, ${}, 0x{:x}", rt, rs, imm);
+        }
+
+        Ok(())
+    }
+
+    pub fn xori(&mut self, rt: u32, rs: u32, imm: u32, print: bool) -> Result<(), GenericError> {
+        self.set_reg(rt, self.reg(rs) ^ imm);
+
+        if print {
+            println!("xori ${}, ${}, 0x{:x}", rt, rs, imm);
+        }
+
+        Ok(())
+    }
+
+    pub fn slti(&mut self, rt: u32, rs: u32, imm: u32, print: bool) -> Result<(), GenericError> {
+        let less = (self.reg(rs) as i32) < (imm as i32);
+        self.set_reg(rt, less as u32);
+
+        if print {
+            println!("slti ${}, ${}, {}", rt, rs, imm as i32);
+        }
+
+        Ok(())
+    }
+
+    pub fn sltiu(&mut self, rt: u32, rs: u32, imm: u32, print: bool) -> Result<(), GenericError> {
+        // The immediate is sign extended, but compared as unsigned
+        let less = self.reg(rs) < imm;
+        self.set_reg(rt, less as u32);
+
+        if print {
+            println!("sltiu ${}, ${}, {}", rt, rs, imm as i32);
+        }
+
+        Ok(())
+    }
+
+    pub fn lw(&mut self, rs: u32, rt: u32, imm: u32, print: bool) -> Result<(), GenericError> {
+        let address = self.reg(rs).wrapping_add(imm);
+        if let Some(value) = self.load32(address) {
+            self.delayed_load(rt, value);
+        }
+
+        if print {
+            println!("lw ${}, {}(${})", rt, imm as i32, rs);
+        }
+
+        Ok(())
+    }
+
+    pub fn lh(&mut self, rs: u32, rt: u32, imm: u32, print: bool) -> Result<(), GenericError> {
+        let address = self.reg(rs).wrapping_add(imm);
+        if let Some(value) = self.load16(address) {
+            self.delayed_load(rt, value as i16 as u32);
+        }
+
+        if print {
+            println!("lh ${}, {}(${})", rt, imm as i32, rs);
+        }
+
+        Ok(())
+    }
+
+    pub fn lhu(&mut self, rs: u32, rt: u32, imm: u32, print: bool) -> Result<(), GenericError> {
+        let address = self.reg(rs).wrapping_add(imm);
+        if let Some(value) = self.load16(address) {
+            self.delayed_load(rt, value as u32);
+        }
+
+        if print {
+            println!("lhu ${}, {}(${})", rt, imm as i32, rs);
+        }
+
+        Ok(())
+    }
+
+    pub fn lb(&mut self, rs: u32, rt: u32, imm: u32, print: bool) -> Result<(), GenericError> {
+        let address = self.reg(rs).wrapping_add(imm);
+        if let Some(value) = self.load8(address) {
+            self.delayed_load(rt, value as i8 as u32);
+        }
+
+        if print {
+            println!("lb ${}, {}(${})", rt, imm as i32, rs);
+        }
+
+        Ok(())
+    }
+
+    pub fn lbu(&mut self, rs: u32, rt: u32, imm: u32, print: bool) -> Result<(), GenericError> {
+        let address = self.reg(rs).wrapping_add(imm);
+        if let Some(value) = self.load8(address) {
+            self.delayed_load(rt, value as u32);
+        }
+
+        if print {
+            println!("lbu ${}, {}(${})", rt, imm as i32, rs);
+        }
+
+        Ok(())
+    }
+
+    pub fn lwl(&mut self, rs: u32, rt: u32, imm: u32, print: bool) -> Result<(), GenericError> {
+        let address = self.reg(rs).wrapping_add(imm);
+        let word = match self.load32(address & !3) {
+            Some(word) => word,
+            None => return Ok(()),
+        };
+        // Unaligned loads can be chained without waiting for the delay
+        let current = self.out_gpr[rt as usize];
+
+        // Merge the most significant bytes of the register with the
+        // aligned word (little endian)
+        let value = match address & 3 {
+            0 => (current & 0x00ffffff) | (word << 24),
+            1 => (current & 0x0000ffff) | (word << 16),
+            2 => (current & 0x000000ff) | (word << 8),
+            _ => word,
+        };
+
+        self.delayed_load(rt, value);
+
+        if print {
+            println!("lwl ${}, {}(${})", rt, imm as i32, rs);
+        }
+
+        Ok(())
+    }
+
+    pub fn lwr(&mut self, rs: u32, rt: u32, imm: u32, print: bool) -> Result<(), GenericError> {
+        let address = self.reg(rs).wrapping_add(imm);
+        let word = match self.load32(address & !3) {
+            Some(word) => word,
+            None => return Ok(()),
+        };
+        // Unaligned loads can be chained without waiting for the delay
+        let current = self.out_gpr[rt as usize];
+
+        // Merge the least significant bytes of the register with the
+        // aligned word (little endian)
+        let value = match address & 3 {
+            0 => word,
+            1 => (current & 0xff000000) | (word >> 8),
+            2 => (current & 0xffff0000) | (word >> 16),
+            _ => (current & 0xffffff00) | (word >> 24),
+        };
+
+        self.delayed_load(rt, value);
+
+        if print {
+            println!("lwr ${}, {}(${})", rt, imm as i32, rs);
+        }
+
+        Ok(())
+    }
+
+    pub fn sh(&mut self, rs: u32, rt: u32, imm: u32, print: bool) -> Result<(), GenericError> {
+        let address = self.reg(rs).wrapping_add(imm);
+
+        self.store16(address, self.reg(rt) as u16);
+
+        if print {
+            println!("sh ${}, {}(${})", rt, imm as i32, rs);
+        }
+
+        Ok(())
+    }
+
+    pub fn sb(&mut self, rs: u32, rt: u32, imm: u32, print: bool) -> Result<(), GenericError> {
+        let address = self.reg(rs).wrapping_add(imm);
+
+        self.store8(address, self.reg(rt) as u8);
+
+        if print {
+            println!("sb ${}, {}(${})", rt, imm as i32, rs);
+        }
+
+        Ok(())
+    }
+
+    pub fn swl(&mut self, rs: u32, rt: u32, imm: u32, print: bool) -> Result<(), GenericError> {
+        let address = self.reg(rs).wrapping_add(imm);
+        let value = self.reg(rt);
+
+        // Stores the most significant bytes of the register into the
+        // aligned word (little endian), leaving its other bytes alone
+        let (word, mask) = match address & 3 {
+            0 => (value >> 24, 0x000000ff),
+            1 => (value >> 16, 0x0000ffff),
+            2 => (value >> 8, 0x00ffffff),
+            _ => (value, 0xffffffff),
+        };
+
+        self.store32_masked(address & !3, word, mask);
+
+        if print {
+            println!("swl ${}, {}(${})", rt, imm as i32, rs);
+        }
+
+        Ok(())
+    }
+
+    pub fn swr(&mut self, rs: u32, rt: u32, imm: u32, print: bool) -> Result<(), GenericError> {
+        let address = self.reg(rs).wrapping_add(imm);
+        let value = self.reg(rt);
+
+        // Stores the least significant bytes of the register into the
+        // aligned word (little endian), leaving its other bytes alone
+        let (word, mask) = match address & 3 {
+            0 => (value, 0xffffffff),
+            1 => (value << 8, 0xffffff00),
+            2 => (value << 16, 0xffff0000),
+            _ => (value << 24, 0xff000000),
+        };
+
+        self.store32_masked(address & !3, word, mask);
+
+        if print {
+            println!("swr ${}, {}(${})", rt, imm as i32, rs);
+        }
+
+        Ok(())
+    }
+
+    pub fn mfc2(&mut self, rt: u32, rd: u32, print: bool) -> Result<(), GenericError> {
+        self.delayed_load(rt, self.gte.read_data(rd));
+
+        if print {
+            println!("mfc2 ${}, ${}", rt, rd);
+        }
+
+        Ok(())
+    }
+
+    pub fn cfc2(&mut self, rt: u32, rd: u32, print: bool) -> Result<(), GenericError> {
+        self.delayed_load(rt, self.gte.read_control(rd));
+
+        if print {
+            println!("cfc2 ${}, ${}", rt, rd);
+        }
+
+        Ok(())
+    }
+
+    pub fn mtc2(&mut self, rt: u32, rd: u32, print: bool) -> Result<(), GenericError> {
+        self.gte.write_data(rd, self.reg(rt));
+
+        if print {
+            println!("mtc2 ${}, ${}", rt, rd);
+        }
+
+        Ok(())
+    }
+
+    pub fn ctc2(&mut self, rt: u32, rd: u32, print: bool) -> Result<(), GenericError> {
+        self.gte.write_control(rd, self.reg(rt));
+
+        if print {
+            println!("ctc2 ${}, ${}", rt, rd);
+        }
+
+        Ok(())
+    }
+
+    pub fn cop2(&mut self, command: u32, print: bool) -> Result<(), GenericError> {
+        self.gte.command(command);
+
+        if print {
+            println!("cop2 0x{:07x}", command);
+        }
+
+        Ok(())
+    }
+
+    pub fn lwc2(&mut self, rs: u32, rt: u32, imm: u32, print: bool) -> Result<(), GenericError> {
+        let address = self.reg(rs).wrapping_add(imm);
+
+        if let Some(value) = self.load32(address) {
+            self.gte.write_data(rt, value);
+        }
+
+        if print {
+            println!("lwc2 ${}, {}(${})", rt, imm as i32, rs);
+        }
+
+        Ok(())
+    }
+
+    pub fn swc2(&mut self, rs: u32, rt: u32, imm: u32, print: bool) -> Result<(), GenericError> {
+        let address = self.reg(rs).wrapping_add(imm);
+
+        self.store32(address, self.gte.read_data(rt));
+
+        if print {
+            println!("swc2 ${}, {}(${})", rt, imm as i32, rs);
+        }
+
+        Ok(())
     }
 }
@@ -5,18 +5,26 @@ pub enum ROpType {
     And,
     Brk,
     Div,
+    Divu,
     Jalr,
     Jr,
     Mfhi,
     Mflo,
+    Mthi,
+    Mtlo,
     Mult,
+    Multu,
     Nop,
     Nor,
     Or,
     Sll,
+    Sllv,
     Slt,
     Sltu,
+    Sra,
+    Srav,
     Srl,
+    Srlv,
     Sub,
     Subu,
     Syscall,
@@ -29,22 +37,30 @@ pub enum IOpType {
     Addiu,
     Andi,
     Beq,
+    Bgez,
+    Bgezal,
+    Bgtz,
     Blez,
+    Bltz,
+    Bltzal,
     Bne,
     Lb,
     Lbu,
-    Ldc1,
     Lh,
     Lhu,
     Lui,
     Lw,
-    Lwc1,
+    Lwl,
+    Lwr,
     Ori,
     Sb,
     Sh,
     Slti,
     Sltiu,
     Sw,
+    Swl,
+    Swr,
+    Xori,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -53,21 +69,18 @@ pub enum JOpType {
     Jal,
 }
 
+/// Coprocessor instruction classes, shared by every coprocessor `z`
 #[derive(Debug, Clone, Copy)]
-pub enum FOpType {
-    Addd,
-    Adds,
-    Cvtdw,
-    Cvtsd,
-    Divd,
-    Divs,
-    Mfc1,
-    Movd,
-    Movs,
-    Mtc1,
-    Muld,
-    Muls,
-    Mtc0
+pub enum COpType {
+    Mfc, // MFCz: move from data register
+    Cfc, // CFCz: move from control register
+    Mtc, // MTCz: move to data register
+    Ctc, // CTCz: move to control register
+    Bcf, // BCzF: branch on condition false
+    Bct, // BCzT: branch on condition true
+    Cop, // COPz: coprocessor command
+    Lwc, // LWCz: load word to data register
+    Swc, // SWCz: store word from data register
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -87,17 +100,21 @@ pub enum DecodedInstruction {
         op: IOpType,
         rs: u32,
         rt: u32,
-        imm: u32,
+        imm_se: u32, // Sign-extended immediate
+        imm_ze: u32, // Zero-extended immediate
     },
     J {
         op: JOpType,
         addr: u32,
     },
-    F {
-        op: FOpType,
-        rt: u32,
-        rs: u32,
-        rd: u32,
+    C {
+        op: COpType,
+        cop: u32,     // Coprocessor number
+        rs: u32,      // Base register (LWCz / SWCz)
+        rt: u32,      // CPU register, or coprocessor register for LWCz / SWCz
+        rd: u32,      // Coprocessor register
+        imm_se: u32,  // Sign-extended offset (BCzF / BCzT, LWCz / SWCz)
+        command: u32, // Coprocessor command (COPz)
     },
     E {
         op: EOpType,
@@ -110,10 +127,10 @@ impl DecodedInstruction {
     fn new_r(op: ROpType, word: u32) -> Self {
         Self::R {
             op,
-            rs: ((word & 0x3e00000) >> 21) as u32,
-            rt: ((word & 0x1f0000) >> 16) as u32,
-            rd: ((word & 0xf800) >> 11) as u32,
-            shamt: ((word & 0x7c0) >> 6) as u32,
+            rs: (word & 0x3e00000) >> 21,
+            rt: (word & 0x1f0000) >> 16,
+            rd: (word & 0xf800) >> 11,
+            shamt: (word & 0x7c0) >> 6,
         }
     }
 
@@ -121,9 +138,10 @@ impl DecodedInstruction {
     fn new_i(op: IOpType, word: u32) -> Self {
         Self::I {
             op,
-            rs: ((word & 0x3e00000) >> 21) as u32,
-            rt: ((word & 0x1f0000) >> 16) as u32,
-            imm: (word & 0xffff) as u32,
+            rs: (word & 0x3e00000) >> 21,
+            rt: (word & 0x1f0000) >> 16,
+            imm_se: word as i16 as u32,
+            imm_ze: word & 0xffff,
         }
     }
 
@@ -135,13 +153,16 @@ impl DecodedInstruction {
         }
     }
 
-    /// Constructs a new `F`-type instruction from a word.
-    fn new_f(op: FOpType, word: u32) -> Self {
-        Self::F {
+    /// Constructs a new `C`-type (coprocessor) instruction from a word.
+    fn new_c(op: COpType, word: u32) -> Self {
+        Self::C {
             op,
-            rt: ((word & 0x1f0000) >> 16) as u32,
-            rs: ((word & 0xf800) >> 11) as u32,
-            rd: ((word & 0x7c0) >> 6) as u32,
+            cop: (word >> 26) & 0x3,
+            rs: (word & 0x3e00000) >> 21,
+            rt: (word & 0x1f0000) >> 16,
+            rd: (word & 0xf800) >> 11,
+            imm_se: word as i16 as u32,
+            command: word & 0x1ffffff,
         }
     }
 
@@ -156,18 +177,23 @@ impl DecodedInstruction {
 
 impl From<u32> for DecodedInstruction {
     fn from(word: u32) -> Self {
-        use {EOpType::*, FOpType::*, IOpType::*, JOpType::*, ROpType::*};
+        use {COpType::*, EOpType::*, IOpType::*, JOpType::*, ROpType::*};
         if word == 0 {
             return DecodedInstruction::new_r(Nop, word);
         }
-        let opcode = (word as u32) >> 26;
+        let opcode = word >> 26;
         let funct = word & 0x3f;
         let fmt = ((word & 0x3e00000) >> 21) as usize;
+        let rt = (word & 0x1f0000) >> 16;
         match (opcode, funct, fmt) {
             (0, 0x10, ..) => DecodedInstruction::new_r(Mfhi, word),
             (0, 0x12, ..) => DecodedInstruction::new_r(Mflo, word),
+            (0, 0x11, ..) => DecodedInstruction::new_r(Mthi, word),
+            (0, 0x13, ..) => DecodedInstruction::new_r(Mtlo, word),
             (0, 0x18, ..) => DecodedInstruction::new_r(Mult, word),
+            (0, 0x19, ..) => DecodedInstruction::new_r(Multu, word),
             (0, 0x1A, ..) => DecodedInstruction::new_r(Div, word),
+            (0, 0x1B, ..) => DecodedInstruction::new_r(Divu, word),
             (0, 0x20, ..) => DecodedInstruction::new_r(Add, word),
             (0, 0x21, ..) => DecodedInstruction::new_r(Addu, word),
             (0, 0x22, ..) => DecodedInstruction::new_r(Sub, word),
@@ -178,45 +204,62 @@ impl From<u32> for DecodedInstruction {
             (0, 0x27, ..) => DecodedInstruction::new_r(Nor, word),
             (0, 0x00, ..) => DecodedInstruction::new_r(Sll, word),
             (0, 0x02, ..) => DecodedInstruction::new_r(Srl, word),
+            (0, 0x03, ..) => DecodedInstruction::new_r(Sra, word),
+            (0, 0x04, ..) => DecodedInstruction::new_r(Sllv, word),
+            (0, 0x06, ..) => DecodedInstruction::new_r(Srlv, word),
+            (0, 0x07, ..) => DecodedInstruction::new_r(Srav, word),
             (0, 0x2A, ..) => DecodedInstruction::new_r(Slt, word),
             (0, 0x2B, ..) => DecodedInstruction::new_r(Sltu, word),
             (0, 0x08, ..) => DecodedInstruction::new_r(Jr, word),
             (0, 0x09, ..) => DecodedInstruction::new_r(Jalr, word),
             (0, 0x0C, ..) => DecodedInstruction::new_r(Syscall, word),
             (0, 0x0D, ..) => DecodedInstruction::new_r(Brk, word),
-            (0x11, 0x6, 0x10) => DecodedInstruction::new_f(Movs, word),
-            (0x11, 0x6, 0x11) => DecodedInstruction::new_f(Movd, word),
-            (0x11, 0x0, 0x10) => DecodedInstruction::new_f(Adds, word),
-            (0x11, 0x0, 0x11) => DecodedInstruction::new_f(Addd, word),
-            (0x11, 0x2, 0x10) => DecodedInstruction::new_f(Muls, word),
-            (0x11, 0x2, 0x11) => DecodedInstruction::new_f(Muld, word),
-            (0x11, 0x3, 0x10) => DecodedInstruction::new_f(Divs, word),
-            (0x11, 0x3, 0x11) => DecodedInstruction::new_f(Divd, word),
-            (0x11, 0x20, 0x11) => DecodedInstruction::new_f(Cvtsd, word),
-            (0x11, 0x21, 0x14) => DecodedInstruction::new_f(Cvtdw, word),
-            (0x11, .., 0x0) => DecodedInstruction::new_f(Mfc1, word),
-            (0x11, .., 0x4) => DecodedInstruction::new_f(Mtc1, word),
-            (0x10, ..) => DecodedInstruction::new_f(Mtc0, word),
+            // COP0 - COP3. The PS1 only has COP0 (system control) and COP2
+            // (GTE), the others raise coprocessor unusable exceptions.
+            (0x10..=0x13, _, 0x00) => DecodedInstruction::new_c(Mfc, word),
+            (0x10..=0x13, _, 0x02) => DecodedInstruction::new_c(Cfc, word),
+            (0x10..=0x13, _, 0x04) => DecodedInstruction::new_c(Mtc, word),
+            (0x10..=0x13, _, 0x06) => DecodedInstruction::new_c(Ctc, word),
+            (0x10..=0x13, _, 0x08) => match rt & 0x1 {
+                0 => DecodedInstruction::new_c(Bcf, word),
+                _ => DecodedInstruction::new_c(Bct, word),
+            },
+            (0x10..=0x13, _, 0x10..=0x1f) => DecodedInstruction::new_c(Cop, word),
             (0x08, ..) => DecodedInstruction::new_i(Addi, word),
             (0x09, ..) => DecodedInstruction::new_i(Addiu, word),
             (0x0A, ..) => DecodedInstruction::new_i(Slti, word),
             (0x0B, ..) => DecodedInstruction::new_i(Sltiu, word),
             (0x0C, ..) => DecodedInstruction::new_i(Andi, word),
             (0x0D, ..) => DecodedInstruction::new_i(Ori, word),
+            (0x0E, ..) => DecodedInstruction::new_i(Xori, word),
             (0x04, ..) => DecodedInstruction::new_i(Beq, word),
             (0x05, ..) => DecodedInstruction::new_i(Bne, word),
             (0x06, ..) => DecodedInstruction::new_i(Blez, word),
+            (0x07, ..) => DecodedInstruction::new_i(Bgtz, word),
+            // REGIMM: bit 0 of rt selects bgez over bltz, and the link
+            // variants are selected by rt[4:1] == 0b1000 (other values
+            // behave like the plain branches on the R3000A).
+            (0x01, ..) => match (rt & 0x1, rt & 0x1e == 0x10) {
+                (0, false) => DecodedInstruction::new_i(Bltz, word),
+                (_, false) => DecodedInstruction::new_i(Bgez, word),
+                (0, true) => DecodedInstruction::new_i(Bltzal, word),
+                (_, true) => DecodedInstruction::new_i(Bgezal, word),
+            },
             (0x0F, ..) => DecodedInstruction::new_i(Lui, word),
             (0x20, ..) => DecodedInstruction::new_i(Lb, word),
             (0x21, ..) => DecodedInstruction::new_i(Lh, word),
+            (0x22, ..) => DecodedInstruction::new_i(Lwl, word),
             (0x23, ..) => DecodedInstruction::new_i(Lw, word),
             (0x24, ..) => DecodedInstruction::new_i(Lbu, word),
             (0x25, ..) => DecodedInstruction::new_i(Lhu, word),
+            (0x26, ..) => DecodedInstruction::new_i(Lwr, word),
             (0x2B, ..) => DecodedInstruction::new_i(Sw, word),
             (0x28, ..) => DecodedInstruction::new_i(Sb, word),
             (0x29, ..) => DecodedInstruction::new_i(Sh, word),
-            (0x31, ..) => DecodedInstruction::new_i(Lwc1, word),
-            (0x35, ..) => DecodedInstruction::new_i(Ldc1, word),
+            (0x2A, ..) => DecodedInstruction::new_i(Swl, word),
+            (0x2E, ..) => DecodedInstruction::new_i(Swr, word),
+            (0x30..=0x33, ..) => DecodedInstruction::new_c(Lwc, word),
+            (0x38..=0x3B, ..) => DecodedInstruction::new_c(Swc, word),
             (0x2, ..) => DecodedInstruction::new_j(J, word),
             (0x3, ..) => DecodedInstruction::new_j(Jal, word),
             (..) => DecodedInstruction::new_e(Unknown, word),
@@ -230,3 +273,26 @@ impl Default for DecodedInstruction {
         DecodedInstruction::new_e(Unknown, 0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn immediates_are_sign_and_zero_extended() {
+        match DecodedInstruction::from(0x2405fffc) {
+            // addiu $5, $0, -4
+            DecodedInstruction::I {
+                op: IOpType::Addiu,
+                rs: 0,
+                rt: 5,
+                imm_se,
+                imm_ze,
+            } => {
+                assert_eq!(imm_se, 0xfffffffc);
+                assert_eq!(imm_ze, 0x0000fffc);
+            }
+            _ => panic!("addiu decoded as another instruction"),
+        }
+    }
+}
@@ -0,0 +1,242 @@
+/// Peripherals attached to the seven DMA channels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Port {
+    MdecIn = 0,
+    MdecOut = 1,
+    Gpu = 2,
+    Cdrom = 3,
+    Spu = 4,
+    Pio = 5,
+    Otc = 6, // Ordering table clear
+}
+
+pub const PORTS: [Port; 7] = [
+    Port::MdecIn,
+    Port::MdecOut,
+    Port::Gpu,
+    Port::Cdrom,
+    Port::Spu,
+    Port::Pio,
+    Port::Otc,
+];
+
+/// Register offsets (0x1f801080 - 0x1f8010ff)
+const MADR: u32 = 0x0; // Base address
+const BCR: u32 = 0x4; // Block control
+const CHCR: u32 = 0x8; // Channel control
+const DPCR: u32 = 0x70; // Control
+const DICR: u32 = 0x74; // Interrupt
+
+/// CHCR bits
+const CHCR_FROM_RAM: u32 = 1 << 0;
+const CHCR_BACKWARDS: u32 = 1 << 1;
+const CHCR_START: u32 = 1 << 24;
+const CHCR_TRIGGER: u32 = 1 << 28; // Manual mode only
+const CHCR_WRITABLE_MASK: u32 = 0x71770703;
+
+/// DICR bits
+const DICR_FORCE_IRQ: u32 = 1 << 15;
+const DICR_MASTER_ENABLE: u32 = 1 << 23;
+const DICR_MASTER_FLAG: u32 = 1 << 31;
+const DICR_WRITABLE_MASK: u32 = 0x00ff803f;
+const DICR_FLAGS_MASK: u32 = 0x7f000000; // Acknowledged by writing ones
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SyncMode {
+    Manual,     // All words at once
+    Block,      // Blocks of words, as the peripheral requests them
+    LinkedList, // GPU command lists
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Channel {
+    pub madr: u32,
+    pub bcr: u32,
+    pub chcr: u32,
+}
+
+impl Channel {
+    pub fn sync_mode(&self) -> SyncMode {
+        match (self.chcr >> 9) & 0x3 {
+            0 => SyncMode::Manual,
+            1 => SyncMode::Block,
+            _ => SyncMode::LinkedList,
+        }
+    }
+
+    pub fn reads_ram(&self) -> bool {
+        self.chcr & CHCR_FROM_RAM != 0
+    }
+
+    /// Address increment between words.
+    pub fn step(&self) -> u32 {
+        if self.chcr & CHCR_BACKWARDS != 0 {
+            (-4i32) as u32
+        } else {
+            4
+        }
+    }
+
+    fn active(&self) -> bool {
+        let triggered = self.sync_mode() != SyncMode::Manual || self.chcr & CHCR_TRIGGER != 0;
+
+        self.chcr & CHCR_START != 0 && triggered
+    }
+
+    /// Length of a manual or block transfer, in words.
+    pub fn words(&self) -> u32 {
+        let block_size = match self.bcr & 0xffff {
+            0 => 0x10000,
+            size => size,
+        };
+
+        match self.sync_mode() {
+            SyncMode::Block => block_size * (self.bcr >> 16),
+            _ => block_size,
+        }
+    }
+}
+
+/// DMA controller, transfers are carried out by the bus
+#[derive(Debug, Clone)]
+pub struct Dma {
+    pub channels: [Channel; 7],
+    pub dpcr: u32,
+    pub dicr: u32,
+    irq: bool, // Master flag as last seen, interrupts fire on its rising edge
+}
+
+impl Dma {
+    pub fn new() -> Self {
+        Self {
+            channels: [Channel::default(); 7],
+            dpcr: 0x07654321,
+            dicr: 0,
+            irq: false,
+        }
+    }
+
+    fn master_flag(&self) -> bool {
+        let enabled = (self.dicr >> 16) & 0x7f;
+        let flags = (self.dicr >> 24) & 0x7f;
+
+        self.dicr & DICR_FORCE_IRQ != 0
+            || (self.dicr & DICR_MASTER_ENABLE != 0 && enabled & flags != 0)
+    }
+
+    /// Returns whether the master flag just went up.
+    fn update_irq(&mut self) -> bool {
+        let irq = self.master_flag();
+        let rising = irq && !self.irq;
+
+        self.irq = irq;
+        rising
+    }
+
+    /// Enabled channel waiting for its transfer, if any.
+    pub fn active_channel(&self) -> Option<usize> {
+        (0..self.channels.len())
+            .find(|&index| self.dpcr & (0x8 << (index * 4)) != 0 && self.channels[index].active())
+    }
+
+    /// Marks a transfer as complete, returns whether it raises an interrupt.
+    pub fn finish(&mut self, index: usize) -> bool {
+        self.channels[index].chcr &= !(CHCR_START | CHCR_TRIGGER);
+
+        if self.dicr & (1 << (16 + index)) != 0 {
+            self.dicr |= 1 << (24 + index);
+        }
+
+        self.update_irq()
+    }
+
+    pub fn load(&self, offset: u32) -> u32 {
+        match offset {
+            DPCR => self.dpcr,
+            DICR => {
+                if self.master_flag() {
+                    self.dicr | DICR_MASTER_FLAG
+                } else {
+                    self.dicr
+                }
+            }
+            _ => match self.channels.get((offset >> 4) as usize) {
+                Some(channel) => match offset & 0xf {
+                    MADR => channel.madr,
+                    BCR => channel.bcr,
+                    CHCR => channel.chcr,
+                    _ => 0,
+                },
+                None => 0,
+            },
+        }
+    }
+
+    /// Full register value for a narrow store of `value` on the byte lanes
+    /// in `lanes`. The bytes not written keep their value, without
+    /// acknowledging DICR flags.
+    pub fn widen(&self, offset: u32, value: u32, lanes: u32) -> u32 {
+        let mut current = self.load(offset);
+
+        if offset == DICR {
+            current &= !(DICR_FLAGS_MASK | DICR_MASTER_FLAG);
+        }
+
+        (current & !lanes) | value
+    }
+
+    /// Returns whether the write raises an interrupt.
+    pub fn store(&mut self, offset: u32, value: u32) -> bool {
+        match offset {
+            DPCR => self.dpcr = value,
+            DICR => {
+                let flags = self.dicr & DICR_FLAGS_MASK & !value;
+                self.dicr = (value & DICR_WRITABLE_MASK) | flags;
+
+                return self.update_irq();
+            }
+            _ => {
+                let index = (offset >> 4) as usize;
+
+                if let Some(channel) = self.channels.get_mut(index) {
+                    match offset & 0xf {
+                        MADR => channel.madr = value & 0xffffff,
+                        BCR => channel.bcr = value,
+                        CHCR if PORTS[index] == Port::Otc => {
+                            // Always backwards, to RAM and in manual mode
+                            channel.chcr = (value & (CHCR_START | CHCR_TRIGGER)) | CHCR_BACKWARDS
+                        }
+                        CHCR => channel.chcr = value & CHCR_WRITABLE_MASK,
+                        _ => (),
+                    }
+                }
+            }
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interrupt_fires_on_the_master_flag_rising_edge() {
+        let mut dma = Dma::new();
+
+        // Channel 2 IRQ and master enable
+        assert!(!dma.store(DICR, 0x00840000));
+        assert!(dma.finish(2));
+
+        // Still high, no new edge
+        assert!(!dma.finish(2));
+        assert_ne!(dma.load(DICR) & DICR_MASTER_FLAG, 0);
+
+        // Acknowledging the flag lowers it
+        assert!(!dma.store(DICR, 0x04840000));
+        assert_eq!(dma.load(DICR), 0x00840000);
+
+        assert!(dma.store(DICR, 0x00840000 | DICR_FORCE_IRQ));
+    }
+}
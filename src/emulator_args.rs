@@ -4,6 +4,13 @@ use clap::{App, Arg};
 pub struct EmulatorArgs {
     pub bios: String,
     pub debug: bool,
+    pub dev_kit_ram: bool,
+    pub dump_dir: String,
+    pub dump_frames: bool,
+    pub dump_vram: bool,
+    pub dump_every: u64,
+    pub dump_audio: Option<String>,
+    pub frames: Option<u64>,
 }
 
 pub fn parse_emulator_args() -> EmulatorArgs {
@@ -25,10 +32,69 @@ pub fn parse_emulator_args() -> EmulatorArgs {
                 .long("debug")
                 .about("Sets the debug flag"),
         )
+        .arg(
+            Arg::new("dev-kit-ram")
+                .long("dev-kit-ram")
+                .about("Emulates the 8 MiB RAM of DTL-H2000 dev kits"),
+        )
+        .arg(
+            Arg::new("dump-frames")
+                .long("dump-frames")
+                .about("Saves the display area as numbered PPM images"),
+        )
+        .arg(
+            Arg::new("dump-vram")
+                .long("dump-vram")
+                .about("Saves the whole 1024x512 VRAM as numbered PPM images"),
+        )
+        .arg(
+            Arg::new("dump-dir")
+                .long("dump-dir")
+                .value_name("DIR")
+                .default_value(".")
+                .about("Sets the directory dumped images are written to"),
+        )
+        .arg(
+            Arg::new("dump-every")
+                .long("dump-every")
+                .value_name("N")
+                .default_value("60")
+                .about("Dumps images every N frames"),
+        )
+        .arg(
+            Arg::new("dump-audio")
+                .long("dump-audio")
+                .value_name("FILE")
+                .about("Streams the sound output to a 44.1 kHz stereo WAV file"),
+        )
+        .arg(
+            Arg::new("frames")
+                .long("frames")
+                .value_name("N")
+                .about("Exits after emulating N frames"),
+        )
         .get_matches();
 
     EmulatorArgs {
         bios: matches.value_of("bios").unwrap_or_default().to_owned(),
         debug: matches.is_present("debug"),
+        dev_kit_ram: matches.is_present("dev-kit-ram"),
+        dump_dir: matches.value_of("dump-dir").unwrap_or(".").to_owned(),
+        dump_frames: matches.is_present("dump-frames"),
+        dump_vram: matches.is_present("dump-vram"),
+        dump_every: matches
+            .value_of_t::<u64>("dump-every")
+            .unwrap_or_else(|err| err.exit())
+            .max(1),
+        dump_audio: matches.value_of("dump-audio").map(str::to_owned),
+        frames: if matches.is_present("frames") {
+            Some(
+                matches
+                    .value_of_t("frames")
+                    .unwrap_or_else(|err| err.exit()),
+            )
+        } else {
+            None
+        },
     }
 }
@@ -0,0 +1,43 @@
+use crate::gpu::{Frame, Gpu};
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+/// Writes a frame as a binary PPM image.
+pub fn write_ppm(path: &Path, frame: &Frame) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+
+    write!(file, "P6\n{} {}\n255\n", frame.width, frame.height)?;
+
+    for pixel in &frame.pixels {
+        file.write_all(&[(pixel >> 16) as u8, (pixel >> 8) as u8, *pixel as u8])?;
+    }
+
+    file.flush()
+}
+
+/// Saves the display area and/or the whole VRAM as numbered images in
+/// `directory`.
+pub fn dump_frame(
+    directory: &str,
+    number: u64,
+    gpu: &Gpu,
+    display: bool,
+    vram: bool,
+) -> io::Result<()> {
+    let directory = Path::new(directory);
+
+    if display {
+        let path = directory.join(format!("frame_{:06}.ppm", number));
+        write_ppm(&path, &gpu.display_frame())?;
+    }
+
+    if vram {
+        let path = directory.join(format!("vram_{:06}.ppm", number));
+        write_ppm(&path, &gpu.vram_frame())?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,991 @@
+use crate::interrupt_controller::{Interrupt, InterruptController};
+
+/// 1 MiB of VRAM, as 1024x512 16-bit pixels
+pub const VRAM_WIDTH: usize = 1024;
+pub const VRAM_HEIGHT: usize = 512;
+
+/// Texture color depth
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextureDepth {
+    Clut4,  // 4-bit indices into a 16 color CLUT
+    Clut8,  // 8-bit indices into a 256 color CLUT
+    Direct, // 15-bit colors
+}
+
+/// Semi-transparency: how a pixel (F) is combined with the one behind it (B)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlendMode {
+    Average,    // B / 2 + F / 2
+    Add,        // B + F
+    Subtract,   // B - F
+    AddQuarter, // B + F / 4
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    /// Decodes the 24-bit color of a GP0 command word.
+    pub fn from_command(word: u32) -> Self {
+        Self {
+            r: word as u8,
+            g: (word >> 8) as u8,
+            b: (word >> 16) as u8,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Vertex {
+    pub x: i32,
+    pub y: i32,
+    pub color: Color,
+    pub u: u8,
+    pub v: u8,
+}
+
+/// Texture page and CLUT a primitive samples from
+#[derive(Debug, Clone, Copy)]
+pub struct Texture {
+    pub page_x: u32,
+    pub page_y: u32,
+    pub depth: TextureDepth,
+    pub clut_x: u32,
+    pub clut_y: u32,
+}
+
+/// Per-primitive drawing settings
+#[derive(Debug, Clone, Copy)]
+pub struct Attributes {
+    pub semi_transparent: bool,
+    pub blend_mode: BlendMode,
+    pub texture: Option<Texture>,
+    pub raw_texture: bool, // Texels are not modulated by the vertex color
+    pub dither: bool,
+}
+
+/// Image shown on screen, as 0x00RRGGBB pixels row by row
+#[derive(Debug, Clone, Default)]
+pub struct Frame {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u32>,
+}
+
+/// Expands a 15-bit VRAM pixel to 0x00RRGGBB.
+pub fn rgb888(pixel: u16) -> u32 {
+    let expand = |component: u16| {
+        let component = (component & 0x1f) as u32;
+        (component << 3) | (component >> 2)
+    };
+
+    (expand(pixel) << 16) | (expand(pixel >> 5) << 8) | expand(pixel >> 10)
+}
+
+/// Rectangle of VRAM copied to or from the CPU one pixel at a time
+#[derive(Debug, Clone, Copy)]
+pub struct Transfer {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub column: u32,
+    pub row: u32,
+}
+
+impl Transfer {
+    /// Decodes the position and size words of GP0 0xa0 / 0xc0.
+    pub fn new(position: u32, size: u32) -> Self {
+        let (width, height) = transfer_size(size);
+
+        Self {
+            x: position & 0x3ff,
+            y: (position >> 16) & 0x1ff,
+            width,
+            height,
+            column: 0,
+            row: 0,
+        }
+    }
+
+    /// VRAM coordinates of the next pixel, `None` once the whole rectangle
+    /// has been transferred.
+    pub fn next_pixel(&mut self) -> Option<(u32, u32)> {
+        if self.row == self.height {
+            return None;
+        }
+
+        let pixel = (self.x + self.column, self.y + self.row);
+
+        self.column += 1;
+
+        if self.column == self.width {
+            self.column = 0;
+            self.row += 1;
+        }
+
+        Some(pixel)
+    }
+
+    pub fn done(&self) -> bool {
+        self.row == self.height
+    }
+}
+
+/// Software GPU (GP0 0x1f801810, GP1 0x1f801814)
+#[derive(Debug, Clone)]
+pub struct Gpu {
+    pub vram: Vec<u16>,
+    pub command: Vec<u32>,            // GP0 command being received
+    pub gpuread: u32,                 // GPUREAD latch
+    pub vram_write: Option<Transfer>, // CPU to VRAM (GP0 0xa0)
+    pub vram_read: Option<Transfer>,  // VRAM to CPU (GP0 0xc0)
+
+    // Drawing state (GP0 0xe1 - 0xe6)
+    pub draw_mode: u32, // Texture page, blending, dithering, ...
+    pub texture_window_mask_x: u32,
+    pub texture_window_mask_y: u32,
+    pub texture_window_offset_x: u32,
+    pub texture_window_offset_y: u32,
+    pub drawing_area_left: i32,
+    pub drawing_area_top: i32,
+    pub drawing_area_right: i32,
+    pub drawing_area_bottom: i32,
+    pub drawing_offset_x: i32,
+    pub drawing_offset_y: i32,
+    pub set_mask: bool,   // Drawn pixels get bit 15 set
+    pub check_mask: bool, // Pixels with bit 15 set are not drawn over
+
+    // Display state (GP1)
+    pub irq: bool,
+    pub display_disabled: bool,
+    pub dma_direction: u32,
+    pub display_start_x: u32,
+    pub display_start_y: u32,
+    pub display_range_x1: u32,
+    pub display_range_x2: u32,
+    pub display_range_y1: u32,
+    pub display_range_y2: u32,
+    pub display_mode: u32, // GP1 0x08 bits 0-7
+    pub field: bool,       // Odd field of an interlaced frame
+    pub odd_line: bool,    // Scanline being output (GPUSTAT bit 31)
+}
+
+impl Gpu {
+    pub fn new() -> Self {
+        let mut gpu = Self {
+            vram: vec![0; VRAM_WIDTH * VRAM_HEIGHT],
+            command: Vec::new(),
+            gpuread: 0,
+            vram_write: None,
+            vram_read: None,
+            draw_mode: 0,
+            texture_window_mask_x: 0,
+            texture_window_mask_y: 0,
+            texture_window_offset_x: 0,
+            texture_window_offset_y: 0,
+            drawing_area_left: 0,
+            drawing_area_top: 0,
+            drawing_area_right: 0,
+            drawing_area_bottom: 0,
+            drawing_offset_x: 0,
+            drawing_offset_y: 0,
+            set_mask: false,
+            check_mask: false,
+            irq: false,
+            display_disabled: true,
+            dma_direction: 0,
+            display_start_x: 0,
+            display_start_y: 0,
+            display_range_x1: 0,
+            display_range_x2: 0,
+            display_range_y1: 0,
+            display_range_y2: 0,
+            display_mode: 0,
+            field: false,
+            odd_line: false,
+        };
+
+        gpu.reset();
+        gpu
+    }
+
+    /// GP1 0x00
+    fn reset(&mut self) {
+        self.reset_command_buffer();
+        self.draw_mode = 0;
+        self.texture_window_mask_x = 0;
+        self.texture_window_mask_y = 0;
+        self.texture_window_offset_x = 0;
+        self.texture_window_offset_y = 0;
+        self.drawing_area_left = 0;
+        self.drawing_area_top = 0;
+        self.drawing_area_right = 0;
+        self.drawing_area_bottom = 0;
+        self.drawing_offset_x = 0;
+        self.drawing_offset_y = 0;
+        self.set_mask = false;
+        self.check_mask = false;
+        self.irq = false;
+        self.display_disabled = true;
+        self.dma_direction = 0;
+        self.display_start_x = 0;
+        self.display_start_y = 0;
+        self.display_range_x1 = 0x200;
+        self.display_range_x2 = 0x200 + 256 * 10;
+        self.display_range_y1 = 0x10;
+        self.display_range_y2 = 0x10 + 240;
+        self.display_mode = 0;
+        self.field = false;
+        self.odd_line = false;
+    }
+
+    /// GP1 0x01: drops any partially received command or image, and any
+    /// pending VRAM read.
+    fn reset_command_buffer(&mut self) {
+        self.command.clear();
+        self.vram_write = None;
+        self.vram_read = None;
+    }
+
+    pub fn vram(&self, x: u32, y: u32) -> u16 {
+        self.vram[(y as usize % VRAM_HEIGHT) * VRAM_WIDTH + (x as usize % VRAM_WIDTH)]
+    }
+
+    pub fn set_vram(&mut self, x: u32, y: u32, pixel: u16) {
+        self.vram[(y as usize % VRAM_HEIGHT) * VRAM_WIDTH + (x as usize % VRAM_WIDTH)] = pixel;
+    }
+
+    /// Writes a pixel coming from a transfer, honouring the mask bit settings.
+    fn transfer_pixel(&mut self, x: u32, y: u32, pixel: u16) {
+        if self.check_mask && self.vram(x, y) & 0x8000 != 0 {
+            return;
+        }
+
+        self.set_vram(x, y, pixel | (self.set_mask as u16) << 15);
+    }
+
+    pub fn interlaced(&self) -> bool {
+        self.display_mode & (1 << 5) != 0
+    }
+
+    pub fn pal(&self) -> bool {
+        self.display_mode & (1 << 3) != 0
+    }
+
+    /// GPU clocks per dot for the horizontal resolution
+    pub fn dot_clock_divider(&self) -> u32 {
+        if self.display_mode & (1 << 6) != 0 {
+            return 7; // 368
+        }
+
+        match self.display_mode & 0x3 {
+            0 => 10, // 256
+            1 => 8,  // 320
+            2 => 5,  // 512
+            _ => 4,  // 640
+        }
+    }
+
+    /// Interlaced 480-line modes draw even and odd lines in alternate fields.
+    pub fn start_frame(&mut self) {
+        self.field = self.interlaced() && !self.field;
+    }
+
+    /// Updates GPUSTAT bit 31 for the scanline being output.
+    pub fn set_line(&mut self, line: u32, vblank: bool) {
+        let interlaced_480 = self.interlaced() && self.display_mode & (1 << 2) != 0;
+
+        self.odd_line = if vblank {
+            false
+        } else if interlaced_480 {
+            self.field
+        } else {
+            line & 1 != 0
+        };
+    }
+
+    /// Renders the display area: `display_start` in VRAM, sized by the
+    /// display range and the horizontal resolution.
+    pub fn display_frame(&self) -> Frame {
+        let range_x = self.display_range_x2.saturating_sub(self.display_range_x1);
+        let range_y = self.display_range_y2.saturating_sub(self.display_range_y1);
+
+        // Widths are rounded to 4 pixels
+        let width = ((range_x / self.dot_clock_divider() + 2) & !3).min(VRAM_WIDTH as u32);
+        let mut height = range_y;
+
+        if self.interlaced() && self.display_mode & (1 << 2) != 0 {
+            height *= 2;
+        }
+
+        let height = height.min(VRAM_HEIGHT as u32);
+        let mut pixels = vec![0; (width * height) as usize];
+
+        if !self.display_disabled {
+            let color_24bit = self.display_mode & (1 << 4) != 0;
+
+            for y in 0..height {
+                for x in 0..width {
+                    let row = self.display_start_y + y;
+
+                    pixels[(y * width + x) as usize] = if color_24bit {
+                        // Three bytes per pixel, packed across halfwords
+                        let offset = self.display_start_x * 2 + x * 3;
+                        let byte = |offset: u32| {
+                            let halfword = self.vram(offset / 2, row);
+                            (halfword >> ((offset & 1) * 8)) as u32 & 0xff
+                        };
+
+                        (byte(offset) << 16) | (byte(offset + 1) << 8) | byte(offset + 2)
+                    } else {
+                        rgb888(self.vram(self.display_start_x + x, row))
+                    };
+                }
+            }
+        }
+
+        Frame {
+            width: width as usize,
+            height: height as usize,
+            pixels,
+        }
+    }
+
+    /// The whole VRAM, as 15-bit pixels
+    pub fn vram_frame(&self) -> Frame {
+        Frame {
+            width: VRAM_WIDTH,
+            height: VRAM_HEIGHT,
+            pixels: self.vram.iter().map(|&pixel| rgb888(pixel)).collect(),
+        }
+    }
+
+    /// GPUSTAT
+    pub fn status(&self) -> u32 {
+        let mut status = self.draw_mode & 0x7ff;
+
+        status |= (self.set_mask as u32) << 11;
+        status |= (self.check_mask as u32) << 12;
+        status |= ((!self.interlaced() || self.field) as u32) << 13;
+        status |= ((self.draw_mode >> 11) & 0x1) << 15;
+        status |= ((self.display_mode >> 6) & 0x1) << 16;
+        status |= (self.display_mode & 0x3f) << 17;
+        status |= (self.display_disabled as u32) << 23;
+        status |= (self.irq as u32) << 24;
+
+        // Commands execute as soon as they are complete
+        let idle = self.command.is_empty() && self.vram_write.is_none();
+        let ready_command = idle;
+        let ready_read = self.vram_read.is_some();
+        let ready_dma = idle || self.vram_write.is_some();
+
+        let request = match self.dma_direction {
+            0 => false,
+            1 | 2 => ready_dma,
+            _ => ready_read,
+        };
+
+        status |= (request as u32) << 25;
+        status |= (ready_command as u32) << 26;
+        status |= (ready_read as u32) << 27;
+        status |= (ready_dma as u32) << 28;
+        status |= self.dma_direction << 29;
+        status |= (self.odd_line as u32) << 31;
+
+        status
+    }
+
+    /// GPUREAD
+    pub fn read(&mut self) -> u32 {
+        if let Some(mut transfer) = self.vram_read.take() {
+            let mut word = 0;
+
+            for half in 0..2 {
+                if let Some((x, y)) = transfer.next_pixel() {
+                    word |= (self.vram(x, y) as u32) << (half * 16);
+                }
+            }
+
+            if !transfer.done() {
+                self.vram_read = Some(transfer);
+            }
+
+            self.gpuread = word;
+        }
+
+        self.gpuread
+    }
+
+    pub fn gp0(&mut self, word: u32, interrupts: &mut InterruptController) {
+        // Image data, two pixels per word
+        if let Some(mut transfer) = self.vram_write.take() {
+            for pixel in &[word as u16, (word >> 16) as u16] {
+                if let Some((x, y)) = transfer.next_pixel() {
+                    self.transfer_pixel(x, y, *pixel);
+                }
+            }
+
+            if !transfer.done() {
+                self.vram_write = Some(transfer);
+            }
+
+            return;
+        }
+
+        self.command.push(word);
+
+        if !self.command_complete() {
+            return;
+        }
+
+        let mut command = std::mem::take(&mut self.command);
+        let opcode = command[0] >> 24;
+
+        if is_polyline(opcode) {
+            command.pop(); // Terminator
+        }
+
+        match opcode {
+            0x00 | 0x01 => (), // Nop, clear texture cache
+            0x02 => self.gp0_fill(&command),
+            0x1f => {
+                self.irq = true;
+                interrupts.request(Interrupt::Gpu);
+            }
+            0x20..=0x3f => self.gp0_polygon(&command),
+            0x40..=0x5f => self.gp0_line(&command),
+            0x60..=0x7f => self.gp0_rectangle(&command),
+            0x80..=0x9f => self.gp0_copy(&command),
+            0xa0..=0xbf => self.vram_write = Some(Transfer::new(command[1], command[2])),
+            0xc0..=0xdf => self.vram_read = Some(Transfer::new(command[1], command[2])),
+            0xe1 => self.draw_mode = word & 0x3fff,
+            0xe2 => {
+                self.texture_window_mask_x = word & 0x1f;
+                self.texture_window_mask_y = (word >> 5) & 0x1f;
+                self.texture_window_offset_x = (word >> 10) & 0x1f;
+                self.texture_window_offset_y = (word >> 15) & 0x1f;
+            }
+            0xe3 => {
+                self.drawing_area_left = (word & 0x3ff) as i32;
+                self.drawing_area_top = ((word >> 10) & 0x1ff) as i32;
+            }
+            0xe4 => {
+                self.drawing_area_right = (word & 0x3ff) as i32;
+                self.drawing_area_bottom = ((word >> 10) & 0x1ff) as i32;
+            }
+            0xe5 => {
+                self.drawing_offset_x = sign_extend_11(word);
+                self.drawing_offset_y = sign_extend_11(word >> 11);
+            }
+            0xe6 => {
+                self.set_mask = word & 0x1 != 0;
+                self.check_mask = word & 0x2 != 0;
+            }
+            _ => (),
+        }
+    }
+
+    /// Whether the words received so far make up a whole command.
+    fn command_complete(&self) -> bool {
+        let opcode = self.command[0] >> 24;
+        let length = self.command.len();
+
+        if is_polyline(opcode) {
+            // Terminated by 0x5xxx5xxx where the next vertex (or color, for
+            // Gouraud shaded lines) would go, after at least two vertices.
+            let last = self.command[length - 1];
+            let gouraud = opcode & 0x10 != 0;
+            let position = if gouraud {
+                length >= 5 && length % 2 == 1
+            } else {
+                length >= 4
+            };
+
+            return position && last & 0xf000f000 == 0x50005000;
+        }
+
+        length >= command_length(opcode)
+    }
+
+    fn gp0_polygon(&mut self, command: &[u32]) {
+        let opcode = command[0] >> 24;
+        let gouraud = opcode & 0x10 != 0;
+        let quad = opcode & 0x08 != 0;
+        let textured = opcode & 0x04 != 0;
+        let count = if quad { 4 } else { 3 };
+
+        let mut vertices = [Vertex::default(); 4];
+        let mut color = command[0];
+        let mut clut = 0;
+        let mut index = 1;
+
+        for (n, vertex) in vertices.iter_mut().enumerate().take(count) {
+            if gouraud && n > 0 {
+                color = command[index];
+                index += 1;
+            }
+
+            *vertex = self.vertex(command[index], color);
+            index += 1;
+
+            if textured {
+                let word = command[index];
+                index += 1;
+
+                vertex.u = word as u8;
+                vertex.v = (word >> 8) as u8;
+
+                match n {
+                    0 => clut = word >> 16,
+                    // The texture page of a polygon replaces the current one
+                    1 => self.draw_mode = (self.draw_mode & !0x9ff) | ((word >> 16) & 0x9ff),
+                    _ => (),
+                }
+            }
+        }
+
+        let raw_texture = opcode & 0x01 != 0;
+        let attributes = Attributes {
+            semi_transparent: opcode & 0x02 != 0,
+            blend_mode: self.blend_mode(),
+            texture: if textured {
+                Some(self.texture(clut))
+            } else {
+                None
+            },
+            raw_texture,
+            dither: self.dithering() && (gouraud || (textured && !raw_texture)),
+        };
+
+        self.draw_triangle([vertices[0], vertices[1], vertices[2]], &attributes);
+
+        if quad {
+            self.draw_triangle([vertices[1], vertices[2], vertices[3]], &attributes);
+        }
+    }
+
+    fn gp0_line(&mut self, command: &[u32]) {
+        let opcode = command[0] >> 24;
+        let gouraud = opcode & 0x10 != 0;
+
+        let vertices: Vec<Vertex> = if gouraud {
+            // Color and vertex pairs, the first color sharing the command word
+            command
+                .chunks(2)
+                .filter(|pair| pair.len() == 2)
+                .map(|pair| self.vertex(pair[1], pair[0]))
+                .collect()
+        } else {
+            command[1..]
+                .iter()
+                .map(|&word| self.vertex(word, command[0]))
+                .collect()
+        };
+
+        let attributes = Attributes {
+            semi_transparent: opcode & 0x02 != 0,
+            blend_mode: self.blend_mode(),
+            texture: None,
+            raw_texture: false,
+            dither: self.dithering() && gouraud,
+        };
+
+        for segment in vertices.windows(2) {
+            self.draw_line(segment[0], segment[1], &attributes);
+        }
+    }
+
+    fn gp0_rectangle(&mut self, command: &[u32]) {
+        let opcode = command[0] >> 24;
+        let textured = opcode & 0x04 != 0;
+
+        let mut origin = self.vertex(command[1], command[0]);
+        let mut clut = 0;
+        let mut index = 2;
+
+        if textured {
+            let word = command[index];
+            index += 1;
+
+            origin.u = word as u8;
+            origin.v = (word >> 8) as u8;
+            clut = word >> 16;
+        }
+
+        let (width, height) = match (opcode >> 3) & 0x3 {
+            0 => (command[index] & 0x3ff, (command[index] >> 16) & 0x1ff),
+            1 => (1, 1),
+            2 => (8, 8),
+            _ => (16, 16),
+        };
+
+        let attributes = Attributes {
+            semi_transparent: opcode & 0x02 != 0,
+            blend_mode: self.blend_mode(),
+            texture: if textured {
+                Some(self.texture(clut))
+            } else {
+                None
+            },
+            raw_texture: opcode & 0x01 != 0,
+            dither: false, // Rectangles are never dithered
+        };
+
+        self.draw_rectangle(origin, width as i32, height as i32, &attributes);
+    }
+
+    /// Fills a rectangle with a solid color, ignoring the drawing area and
+    /// mask bit settings.
+    fn gp0_fill(&mut self, command: &[u32]) {
+        let color = Color::from_command(command[0]);
+        let pixel =
+            (color.r >> 3) as u16 | ((color.g >> 3) as u16) << 5 | ((color.b >> 3) as u16) << 10;
+
+        let x = command[1] & 0x3f0;
+        let y = (command[1] >> 16) & 0x1ff;
+        let width = ((command[2] & 0x3ff) + 0xf) & !0xf;
+        let height = (command[2] >> 16) & 0x1ff;
+
+        for row in 0..height {
+            for column in 0..width {
+                self.set_vram(x + column, y + row, pixel);
+            }
+        }
+    }
+
+    /// Copies a rectangle of VRAM, pixel by pixel from the top left.
+    fn gp0_copy(&mut self, command: &[u32]) {
+        let source_x = command[1] & 0x3ff;
+        let source_y = (command[1] >> 16) & 0x1ff;
+        let destination_x = command[2] & 0x3ff;
+        let destination_y = (command[2] >> 16) & 0x1ff;
+        let (width, height) = transfer_size(command[3]);
+
+        for row in 0..height {
+            for column in 0..width {
+                let pixel = self.vram(source_x + column, source_y + row);
+
+                self.transfer_pixel(destination_x + column, destination_y + row, pixel);
+            }
+        }
+    }
+
+    pub fn gp1(&mut self, word: u32) {
+        match word >> 24 {
+            0x00 => self.reset(),
+            0x01 => self.reset_command_buffer(),
+            0x02 => self.irq = false,
+            0x03 => self.display_disabled = word & 0x1 != 0,
+            0x04 => self.dma_direction = word & 0x3,
+            0x05 => {
+                self.display_start_x = word & 0x3fe;
+                self.display_start_y = (word >> 10) & 0x1ff;
+            }
+            0x06 => {
+                self.display_range_x1 = word & 0xfff;
+                self.display_range_x2 = (word >> 12) & 0xfff;
+            }
+            0x07 => {
+                self.display_range_y1 = word & 0x3ff;
+                self.display_range_y2 = (word >> 10) & 0x3ff;
+            }
+            0x08 => self.display_mode = word & 0xff,
+            0x10..=0x1f => self.gpu_info(word & 0x7),
+            _ => (),
+        }
+    }
+
+    /// GP1 0x10: latches internal registers into GPUREAD.
+    fn gpu_info(&mut self, index: u32) {
+        self.gpuread = match index {
+            0x2 => {
+                self.texture_window_mask_x
+                    | (self.texture_window_mask_y << 5)
+                    | (self.texture_window_offset_x << 10)
+                    | (self.texture_window_offset_y << 15)
+            }
+            0x3 => self.drawing_area_left as u32 | ((self.drawing_area_top as u32) << 10),
+            0x4 => self.drawing_area_right as u32 | ((self.drawing_area_bottom as u32) << 10),
+            0x5 => {
+                (self.drawing_offset_x as u32 & 0x7ff)
+                    | ((self.drawing_offset_y as u32 & 0x7ff) << 11)
+            }
+            0x7 => 2, // GPU version
+            _ => self.gpuread,
+        };
+    }
+
+    /// Decodes a vertex word, applying the drawing offset.
+    fn vertex(&self, word: u32, color: u32) -> Vertex {
+        Vertex {
+            x: sign_extend_11(word) + self.drawing_offset_x,
+            y: sign_extend_11(word >> 16) + self.drawing_offset_y,
+            color: Color::from_command(color),
+            u: 0,
+            v: 0,
+        }
+    }
+
+    /// Texture of the current texture page with the given CLUT attribute.
+    fn texture(&self, clut: u32) -> Texture {
+        Texture {
+            page_x: (self.draw_mode & 0xf) * 64,
+            page_y: ((self.draw_mode >> 4) & 0x1) * 256,
+            depth: match (self.draw_mode >> 7) & 0x3 {
+                0 => TextureDepth::Clut4,
+                1 => TextureDepth::Clut8,
+                _ => TextureDepth::Direct,
+            },
+            clut_x: (clut & 0x3f) * 16,
+            clut_y: (clut >> 6) & 0x1ff,
+        }
+    }
+
+    fn blend_mode(&self) -> BlendMode {
+        match (self.draw_mode >> 5) & 0x3 {
+            0 => BlendMode::Average,
+            1 => BlendMode::Add,
+            2 => BlendMode::Subtract,
+            _ => BlendMode::AddQuarter,
+        }
+    }
+
+    fn dithering(&self) -> bool {
+        self.draw_mode & (1 << 9) != 0
+    }
+}
+
+fn sign_extend_11(value: u32) -> i32 {
+    ((value << 21) as i32) >> 21
+}
+
+/// Transfer sizes count from 1, 0 standing for the whole 1024x512 VRAM.
+fn transfer_size(word: u32) -> (u32, u32) {
+    let width = ((word & 0xffff).wrapping_sub(1) & 0x3ff) + 1;
+    let height = ((word >> 16).wrapping_sub(1) & 0x1ff) + 1;
+
+    (width, height)
+}
+
+fn is_polyline(opcode: u32) -> bool {
+    (0x40..=0x5f).contains(&opcode) && opcode & 0x08 != 0
+}
+
+/// Number of words of a GP0 command, polylines excepted.
+fn command_length(opcode: u32) -> usize {
+    match opcode {
+        0x20..=0x3f => {
+            let vertices = if opcode & 0x08 != 0 { 4 } else { 3 };
+            let textured = (opcode & 0x04 != 0) as usize;
+            let gouraud = (opcode & 0x10 != 0) as usize;
+
+            1 + vertices * (1 + textured) + gouraud * (vertices - 1)
+        }
+        0x40..=0x5f => {
+            if opcode & 0x10 != 0 {
+                4
+            } else {
+                3
+            }
+        }
+        0x60..=0x7f => {
+            let textured = (opcode & 0x04 != 0) as usize;
+            let variable_size = ((opcode >> 3) & 0x3 == 0) as usize;
+
+            2 + textured + variable_size
+        }
+        0x02 | 0xa0..=0xdf => 3,
+        0x80..=0x9f => 4,
+        _ => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn send(gpu: &mut Gpu, words: &[u32]) {
+        let mut interrupts = InterruptController::new();
+
+        for &word in words {
+            gpu.gp0(word, &mut interrupts);
+        }
+    }
+
+    /// Rectangle of VRAM, row by row
+    fn dump(gpu: &Gpu, x: u32, y: u32, width: u32, height: u32) -> Vec<u16> {
+        (y..y + height)
+            .flat_map(|y| (x..x + width).map(move |x| (x, y)))
+            .map(|(x, y)| gpu.vram(x, y))
+            .collect()
+    }
+
+    #[test]
+    fn cpu_to_vram_and_back() {
+        let mut gpu = Gpu::new();
+
+        // 3x1 image at (1022, 10), wrapping around the right edge. The last
+        // halfword only pads the data.
+        send(&mut gpu, &[0xa0000000, 0x000a03fe, 0x00010003]);
+        assert_eq!(gpu.status() & (1 << 26), 0);
+        send(&mut gpu, &[0x00020001, 0xdead0003]);
+        assert_ne!(gpu.status() & (1 << 26), 0);
+
+        assert_eq!(dump(&gpu, 1022, 10, 2, 1), [1, 2]);
+        assert_eq!(dump(&gpu, 0, 10, 2, 1), [3, 0]);
+        assert_eq!(dump(&gpu, 1022, 11, 2, 1), [0, 0]);
+
+        send(&mut gpu, &[0xc0000000, 0x000a03fe, 0x00010003]);
+        assert_ne!(gpu.status() & (1 << 27), 0);
+        assert_eq!(gpu.read(), 0x00020001);
+        assert_eq!(gpu.read(), 0x00000003);
+        assert_eq!(gpu.status() & (1 << 27), 0);
+    }
+
+    #[test]
+    fn resets_cancel_vram_reads() {
+        for &reset in [0x00000000, 0x01000000].iter() {
+            let mut gpu = Gpu::new();
+
+            send(&mut gpu, &[0xc0000000, 0x00000000, 0x00010002]);
+            assert_ne!(gpu.status() & (1 << 27), 0);
+
+            gpu.gp1(reset);
+            assert_eq!(gpu.status() & (1 << 27), 0);
+            assert!(gpu.vram_read.is_none());
+        }
+    }
+
+    #[test]
+    fn command_sequence_matches_the_expected_vram_dump() {
+        let mut gpu = Gpu::new();
+
+        send(&mut gpu, &[0xe3000000, 0xe407ffff, 0xe5000000]);
+        // Blue fill, 16x2 at (0, 0)
+        send(&mut gpu, &[0x02ff0000, 0x00000000, 0x00020010]);
+        // Green and white 2x1 image at (32, 0)
+        send(&mut gpu, &[0xa0000000, 0x00000020, 0x00010002, 0x7fff03e0]);
+        // Copied to (40, 1)
+        send(&mut gpu, &[0x80000000, 0x00000020, 0x00010028, 0x00010002]);
+        // Red 4x4 rectangle at (100, 100)
+        send(&mut gpu, &[0x600000ff, 0x00640064, 0x00040004]);
+
+        let mut expected = vec![0; VRAM_WIDTH * VRAM_HEIGHT];
+        let mut set = |x: usize, y: usize, pixel: u32| {
+            expected[y * VRAM_WIDTH + x] = pixel;
+        };
+
+        for (x, y) in (0..16).flat_map(|x| (0..2).map(move |y| (x, y))) {
+            set(x, y, 0x0000ff);
+        }
+
+        for &(x, y) in [(32, 0), (40, 1)].iter() {
+            set(x, y, 0x00ff00);
+            set(x + 1, y, 0xffffff);
+        }
+
+        for (x, y) in (100..104).flat_map(|x| (100..104).map(move |y| (x, y))) {
+            set(x, y, 0xff0000);
+        }
+
+        let frame = gpu.vram_frame();
+        assert_eq!((frame.width, frame.height), (VRAM_WIDTH, VRAM_HEIGHT));
+        assert!(frame.pixels == expected, "VRAM dump differs");
+    }
+
+    #[test]
+    fn fill_rounds_the_width_and_ignores_the_drawing_area() {
+        let mut gpu = Gpu::new();
+
+        // Blue, 17x2 at (0x13, 4): x rounds down to 0x10, width up to 32
+        send(&mut gpu, &[0x02ff0000, 0x00040013, 0x00020011]);
+
+        assert_eq!(dump(&gpu, 0x0f, 4, 34, 1)[..2], [0, 0x7c00]);
+        assert_eq!(dump(&gpu, 0x2f, 5, 2, 1), [0x7c00, 0]);
+        assert_eq!(dump(&gpu, 0x10, 6, 1, 1), [0]);
+    }
+
+    #[test]
+    fn vram_copy_honours_the_mask_bit() {
+        let mut gpu = Gpu::new();
+        gpu.set_vram(0, 0, 0x1234);
+        gpu.set_vram(1, 0, 0x4321);
+        gpu.set_vram(101, 0, 0x8000);
+
+        // Set and check mask
+        send(
+            &mut gpu,
+            &[0xe6000003, 0x80000000, 0x00000000, 0x00000064, 0x00010002],
+        );
+
+        assert_eq!(dump(&gpu, 100, 0, 2, 1), [0x9234, 0x8000]);
+    }
+
+    #[test]
+    fn flat_triangle_covers_top_left_edges() {
+        let mut gpu = Gpu::new();
+
+        send(&mut gpu, &[0xe3000000, 0xe407fc0f, 0xe5000000]);
+        send(&mut gpu, &[0x200000f8, 0x00000000, 0x00000004, 0x00040000]);
+
+        let r = 0x001f;
+        #[rustfmt::skip]
+        let expected = [
+            r, r, r, r, 0,
+            r, r, r, 0, 0,
+            r, r, 0, 0, 0,
+            r, 0, 0, 0, 0,
+            0, 0, 0, 0, 0,
+        ];
+        assert_eq!(dump(&gpu, 0, 0, 5, 5), expected);
+    }
+
+    #[test]
+    fn display_frame_unpacks_24_bit_pixels() {
+        let mut gpu = Gpu::new();
+
+        // Red, green and blue bytes: 33 22 11, then 66 55 44
+        gpu.set_vram(0x10, 0, 0x2233);
+        gpu.set_vram(0x11, 0, 0x6611);
+        gpu.set_vram(0x12, 0, 0x4455);
+
+        gpu.gp1(0x03000000); // Display on
+        gpu.gp1(0x05000010); // Start at (16, 0)
+        gpu.gp1(0x08000011); // 320 pixels, 24-bit
+
+        let frame = gpu.display_frame();
+        assert_eq!((frame.width, frame.height), (320, 240));
+        assert_eq!(frame.pixels[..2], [0x332211, 0x665544]);
+    }
+
+    #[test]
+    fn status_reports_field_and_dma_direction() {
+        let mut gpu = Gpu::new();
+
+        // Not interlaced: bit 13 stays set
+        assert_ne!(gpu.status() & (1 << 13), 0);
+
+        gpu.gp1(0x08000024); // 480 lines, interlaced
+        gpu.start_frame();
+        gpu.set_line(20, false);
+        assert_ne!(gpu.status() & (1 << 13), 0);
+        assert_ne!(gpu.status() & (1 << 31), 0);
+
+        gpu.start_frame();
+        gpu.set_line(21, false);
+        assert_eq!(gpu.status() & (1 << 13), 0);
+        assert_eq!(gpu.status() & (1 << 31), 0);
+
+        gpu.gp1(0x04000002);
+        assert_eq!((gpu.status() >> 29) & 0x3, 2);
+        assert_ne!(gpu.status() & (1 << 25), 0);
+
+        gpu.gp1(0x04000003);
+        assert_eq!(gpu.status() & (1 << 25), 0);
+    }
+}
@@ -0,0 +1,392 @@
+use crate::gpu::{Attributes, BlendMode, Color, Gpu, Texture, TextureDepth, Vertex};
+
+/// Offsets added to 8-bit color components before they are truncated to 5
+/// bits, indexed by the low two bits of y and x
+const DITHER: [[i32; 4]; 4] = [
+    [-4, 0, -3, 1],
+    [2, -2, 3, -1],
+    [-3, 1, -4, 0],
+    [3, -1, 2, -2],
+];
+
+/// Primitives wider or taller than this are not drawn at all
+const MAX_WIDTH: i32 = 1023;
+const MAX_HEIGHT: i32 = 511;
+
+/// Signed area of the parallelogram spanned by `a -> b` and `a -> p`
+fn edge(a: &Vertex, b: &Vertex, x: i32, y: i32) -> i64 {
+    (b.x - a.x) as i64 * (y - a.y) as i64 - (b.y - a.y) as i64 * (x - a.x) as i64
+}
+
+/// Top and left edges own the pixels lying on them, bottom and right edges
+/// do not.
+fn is_top_left(a: &Vertex, b: &Vertex) -> bool {
+    (a.y == b.y && b.x > a.x) || b.y < a.y
+}
+
+/// Interpolates a vertex attribute with barycentric weights.
+fn interpolate(weights: [i64; 3], values: [u8; 3], area: i64) -> i32 {
+    let sum: i64 = (0..3).map(|n| weights[n] * values[n] as i64).sum();
+
+    (sum / area) as i32
+}
+
+impl Gpu {
+    pub fn draw_triangle(&mut self, vertices: [Vertex; 3], attributes: &Attributes) {
+        let [a, mut b, mut c] = vertices;
+        let mut area = edge(&a, &b, c.x, c.y);
+
+        if area == 0 {
+            return;
+        }
+
+        // Rasterize with a consistent winding
+        if area < 0 {
+            std::mem::swap(&mut b, &mut c);
+            area = -area;
+        }
+
+        let min_x = a.x.min(b.x).min(c.x);
+        let max_x = a.x.max(b.x).max(c.x);
+        let min_y = a.y.min(b.y).min(c.y);
+        let max_y = a.y.max(b.y).max(c.y);
+
+        if max_x - min_x > MAX_WIDTH || max_y - min_y > MAX_HEIGHT {
+            return;
+        }
+
+        let biases = [
+            if is_top_left(&b, &c) { 0 } else { -1 },
+            if is_top_left(&c, &a) { 0 } else { -1 },
+            if is_top_left(&a, &b) { 0 } else { -1 },
+        ];
+
+        for y in min_y.max(self.drawing_area_top)..=max_y.min(self.drawing_area_bottom) {
+            for x in min_x.max(self.drawing_area_left)..=max_x.min(self.drawing_area_right) {
+                let weights = [edge(&b, &c, x, y), edge(&c, &a, x, y), edge(&a, &b, x, y)];
+
+                if (0..3).any(|n| weights[n] + biases[n] < 0) {
+                    continue;
+                }
+
+                let color = [
+                    interpolate(weights, [a.color.r, b.color.r, c.color.r], area),
+                    interpolate(weights, [a.color.g, b.color.g, c.color.g], area),
+                    interpolate(weights, [a.color.b, b.color.b, c.color.b], area),
+                ];
+                let texel = attributes.texture.map(|texture| {
+                    let u = interpolate(weights, [a.u, b.u, c.u], area);
+                    let v = interpolate(weights, [a.v, b.v, c.v], area);
+
+                    self.texel(&texture, u as u8, v as u8)
+                });
+
+                self.shade(x, y, color, texel, attributes);
+            }
+        }
+    }
+
+    pub fn draw_rectangle(
+        &mut self,
+        origin: Vertex,
+        width: i32,
+        height: i32,
+        attributes: &Attributes,
+    ) {
+        let Color { r, g, b } = origin.color;
+        let color = [r as i32, g as i32, b as i32];
+
+        let left = origin.x.max(self.drawing_area_left);
+        let right = (origin.x + width - 1).min(self.drawing_area_right);
+        let top = origin.y.max(self.drawing_area_top);
+        let bottom = (origin.y + height - 1).min(self.drawing_area_bottom);
+
+        for y in top..=bottom {
+            for x in left..=right {
+                let texel = attributes.texture.map(|texture| {
+                    let u = origin.u as i32 + (x - origin.x);
+                    let v = origin.v as i32 + (y - origin.y);
+
+                    self.texel(&texture, u as u8, v as u8)
+                });
+
+                self.shade(x, y, color, texel, attributes);
+            }
+        }
+    }
+
+    pub fn draw_line(&mut self, start: Vertex, end: Vertex, attributes: &Attributes) {
+        let dx = end.x - start.x;
+        let dy = end.y - start.y;
+
+        if dx.abs() > MAX_WIDTH || dy.abs() > MAX_HEIGHT {
+            return;
+        }
+
+        let steps = dx.abs().max(dy.abs());
+
+        for step in 0..=steps {
+            let (x, y, weight) = if steps == 0 {
+                (start.x, start.y, 0)
+            } else {
+                (
+                    start.x + (dx * step + steps / 2 * dx.signum()) / steps,
+                    start.y + (dy * step + steps / 2 * dy.signum()) / steps,
+                    step,
+                )
+            };
+
+            if x < self.drawing_area_left
+                || x > self.drawing_area_right
+                || y < self.drawing_area_top
+                || y > self.drawing_area_bottom
+            {
+                continue;
+            }
+
+            let lerp = |from: u8, to: u8| {
+                if steps == 0 {
+                    from as i32
+                } else {
+                    from as i32 + (to as i32 - from as i32) * weight / steps
+                }
+            };
+            let color = [
+                lerp(start.color.r, end.color.r),
+                lerp(start.color.g, end.color.g),
+                lerp(start.color.b, end.color.b),
+            ];
+
+            self.shade(x, y, color, None, attributes);
+        }
+    }
+
+    /// Fetches a texel, after applying the texture window.
+    fn texel(&self, texture: &Texture, u: u8, v: u8) -> u16 {
+        let u = u as u32;
+        let v = v as u32;
+        let u = (u & !(self.texture_window_mask_x * 8))
+            | ((self.texture_window_offset_x & self.texture_window_mask_x) * 8);
+        let v = (v & !(self.texture_window_mask_y * 8))
+            | ((self.texture_window_offset_y & self.texture_window_mask_y) * 8);
+
+        let y = texture.page_y + v;
+
+        match texture.depth {
+            TextureDepth::Clut4 => {
+                let word = self.vram(texture.page_x + u / 4, y);
+                let index = (word >> ((u & 3) * 4)) & 0xf;
+
+                self.vram(texture.clut_x + index as u32, texture.clut_y)
+            }
+            TextureDepth::Clut8 => {
+                let word = self.vram(texture.page_x + u / 2, y);
+                let index = (word >> ((u & 1) * 8)) & 0xff;
+
+                self.vram(texture.clut_x + index as u32, texture.clut_y)
+            }
+            TextureDepth::Direct => self.vram(texture.page_x + u, y),
+        }
+    }
+
+    /// Runs a pixel through texturing, dithering, semi-transparency and the
+    /// mask bit checks, then writes it to VRAM. `color` is 8 bits per
+    /// component.
+    fn shade(
+        &mut self,
+        x: i32,
+        y: i32,
+        color: [i32; 3],
+        texel: Option<u16>,
+        attributes: &Attributes,
+    ) {
+        let background = self.vram(x as u32, y as u32);
+
+        if self.check_mask && background & 0x8000 != 0 {
+            return;
+        }
+
+        let mut color = color;
+        let mut mask = false;
+
+        if let Some(texel) = texel {
+            // Fully transparent
+            if texel == 0 {
+                return;
+            }
+
+            for (n, component) in color.iter_mut().enumerate() {
+                let value = (((texel >> (n * 5)) & 0x1f) << 3) as i32;
+
+                *component = if attributes.raw_texture {
+                    value
+                } else {
+                    // 0x80 leaves the texel unchanged
+                    (value * *component) >> 7
+                };
+            }
+
+            mask = texel & 0x8000 != 0;
+        }
+
+        if attributes.dither {
+            let offset = DITHER[(y & 3) as usize][(x & 3) as usize];
+
+            for component in color.iter_mut() {
+                *component += offset;
+            }
+        }
+
+        let mut front = color.map(|component| component.clamp(0, 255) >> 3);
+
+        // Textured primitives are only blended where the texel asks for it
+        if attributes.semi_transparent && (texel.is_none() || mask) {
+            for (n, component) in front.iter_mut().enumerate() {
+                let back = ((background >> (n * 5)) & 0x1f) as i32;
+
+                *component = match attributes.blend_mode {
+                    BlendMode::Average => (back + *component) / 2,
+                    BlendMode::Add => (back + *component).min(31),
+                    BlendMode::Subtract => (back - *component).max(0),
+                    BlendMode::AddQuarter => (back + *component / 4).min(31),
+                };
+            }
+        }
+
+        let pixel = front[0] as u16
+            | (front[1] as u16) << 5
+            | (front[2] as u16) << 10
+            | ((mask || self.set_mask) as u16) << 15;
+
+        self.set_vram(x as u32, y as u32, pixel);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAIN: Attributes = Attributes {
+        semi_transparent: false,
+        blend_mode: BlendMode::Average,
+        texture: None,
+        raw_texture: false,
+        dither: false,
+    };
+
+    fn gpu() -> Gpu {
+        let mut gpu = Gpu::new();
+
+        gpu.drawing_area_right = 1023;
+        gpu.drawing_area_bottom = 511;
+        gpu
+    }
+
+    /// Vertex at (x, y) with an 8-bit red component
+    fn red(x: i32, y: i32, r: u8) -> Vertex {
+        Vertex {
+            x,
+            y,
+            color: Color { r, g: 0, b: 0 },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn triangle_skips_bottom_and_right_edges() {
+        let mut gpu = gpu();
+
+        gpu.draw_triangle([red(0, 0, 0xff), red(2, 0, 0xff), red(0, 2, 0xff)], &PLAIN);
+
+        let covered: Vec<bool> = (0..9).map(|n| gpu.vram(n % 3, n / 3) != 0).collect();
+        assert_eq!(
+            covered,
+            [true, true, false, true, false, false, false, false, false]
+        );
+    }
+
+    #[test]
+    fn semi_transparency_modes() {
+        // Background red 16, foreground red 16 (0x80 >> 3)
+        let modes = [
+            (BlendMode::Average, 16),
+            (BlendMode::Add, 31),
+            (BlendMode::Subtract, 0),
+            (BlendMode::AddQuarter, 20),
+        ];
+
+        for &(blend_mode, expected) in modes.iter() {
+            let mut gpu = gpu();
+            let attributes = Attributes {
+                semi_transparent: true,
+                blend_mode,
+                ..PLAIN
+            };
+
+            gpu.set_vram(0, 0, 16);
+            gpu.draw_rectangle(red(0, 0, 0x80), 1, 1, &attributes);
+
+            assert_eq!(gpu.vram(0, 0), expected);
+        }
+    }
+
+    #[test]
+    fn mask_bit_is_set_and_checked() {
+        let mut gpu = gpu();
+
+        gpu.set_mask = true;
+        gpu.draw_rectangle(red(0, 0, 0xff), 1, 1, &PLAIN);
+
+        assert_eq!(gpu.vram(0, 0), 0x801f);
+
+        gpu.set_mask = false;
+        gpu.check_mask = true;
+        gpu.set_vram(1, 0, 0x7fff);
+        gpu.draw_rectangle(red(0, 0, 0x08), 2, 1, &PLAIN);
+
+        assert_eq!((gpu.vram(0, 0), gpu.vram(1, 0)), (0x801f, 0x0001));
+    }
+
+    #[test]
+    fn clut4_texels_skip_transparent_entries() {
+        let mut gpu = gpu();
+        let attributes = Attributes {
+            texture: Some(Texture {
+                page_x: 64,
+                page_y: 0,
+                depth: TextureDepth::Clut4,
+                clut_x: 0,
+                clut_y: 256,
+            }),
+            raw_texture: true,
+            ..PLAIN
+        };
+
+        gpu.set_vram(64, 0, 0x0021); // Indices 1, 2, 0, 0
+        gpu.set_vram(1, 256, 0x001f);
+        gpu.set_vram(2, 256, 0x83e0);
+        gpu.set_vram(2, 10, 0x1234);
+
+        gpu.draw_rectangle(red(0, 10, 0), 3, 1, &attributes);
+
+        assert_eq!(
+            (gpu.vram(0, 10), gpu.vram(1, 10), gpu.vram(2, 10)),
+            (0x001f, 0x83e0, 0x1234)
+        );
+    }
+
+    #[test]
+    fn dithering_offsets_by_position() {
+        let mut gpu = gpu();
+        let attributes = Attributes {
+            dither: true,
+            ..PLAIN
+        };
+
+        gpu.draw_rectangle(red(0, 0, 0x80), 4, 1, &attributes);
+
+        // -4, 0, -3 and +1 before truncating 0x80 to 5 bits
+        let row: Vec<u16> = (0..4).map(|x| gpu.vram(x, 0)).collect();
+        assert_eq!(row, [15, 16, 15, 16]);
+    }
+}
@@ -0,0 +1,898 @@
+/// Reciprocal seed table used by the GTE's Newton-Raphson division
+const UNR_TABLE: [u8; 257] = unr_table();
+
+const fn unr_table() -> [u8; 257] {
+    let mut table = [0; 257];
+    let mut i = 0;
+
+    while i < 257 {
+        let value = (0x40000 / (i as i32 + 0x100) + 1) / 2 - 0x101;
+        table[i] = if value > 0 { value as u8 } else { 0 };
+        i += 1;
+    }
+
+    table
+}
+
+/// FLAG bits
+const FLAG_MAC0_NEGATIVE: u32 = 1 << 15;
+const FLAG_MAC0_POSITIVE: u32 = 1 << 16;
+const FLAG_DIVIDE_OVERFLOW: u32 = 1 << 17;
+const FLAG_SZ3_OTZ_SATURATED: u32 = 1 << 18;
+const FLAG_SX2_SATURATED: u32 = 1 << 14;
+const FLAG_SY2_SATURATED: u32 = 1 << 13;
+const FLAG_IR0_SATURATED: u32 = 1 << 12;
+const FLAG_ERROR: u32 = 1 << 31;
+const FLAG_ERROR_MASK: u32 = 0x7f87e000; // Bits that also set the error bit
+const FLAG_WRITE_MASK: u32 = 0x7ffff000;
+
+type Matrix = [[i16; 3]; 3];
+type Vector = [i16; 3];
+
+/// Geometry Transformation Engine (coprocessor 2)
+#[derive(Debug, Clone)]
+pub struct Gte {
+    // Data registers
+    pub v: [Vector; 3],     // Input vectors V0, V1, V2
+    pub rgbc: [u8; 4],      // Color and GPU command code
+    pub otz: u16,           // Average Z value (for ordering tables)
+    pub ir: [i16; 4],       // Intermediate results IR0 - IR3
+    pub sxy: [[i16; 2]; 3], // Screen XY coordinate FIFO
+    pub sz: [u16; 4],       // Screen Z coordinate FIFO
+    pub rgb: [[u8; 4]; 3],  // Color FIFO
+    pub res1: u32,          // Prohibited register (readable and writable)
+    pub mac: [i32; 4],      // Accumulators MAC0 - MAC3
+    pub lzcs: u32,          // Leading zeros/ones count source
+    // Control registers
+    pub rt: Matrix,   // Rotation matrix
+    pub tr: [i32; 3], // Translation vector
+    pub llm: Matrix,  // Light source matrix
+    pub bk: [i32; 3], // Background color
+    pub lcm: Matrix,  // Light color matrix
+    pub fc: [i32; 3], // Far color
+    pub ofx: i32,     // Screen offset X (16.16)
+    pub ofy: i32,     // Screen offset Y (16.16)
+    pub h: u16,       // Projection plane distance
+    pub dqa: i16,     // Depth queing coefficient
+    pub dqb: i32,     // Depth queing offset
+    pub zsf3: i16,    // Average Z scale factor (3 values)
+    pub zsf4: i16,    // Average Z scale factor (4 values)
+    pub flag: u32,    // Calculation errors
+}
+
+impl Gte {
+    pub fn new() -> Self {
+        Self {
+            v: [[0; 3]; 3],
+            rgbc: [0; 4],
+            otz: 0,
+            ir: [0; 4],
+            sxy: [[0; 2]; 3],
+            sz: [0; 4],
+            rgb: [[0; 4]; 3],
+            res1: 0,
+            mac: [0; 4],
+            lzcs: 0,
+            rt: [[0; 3]; 3],
+            tr: [0; 3],
+            llm: [[0; 3]; 3],
+            bk: [0; 3],
+            lcm: [[0; 3]; 3],
+            fc: [0; 3],
+            ofx: 0,
+            ofy: 0,
+            h: 0,
+            dqa: 0,
+            dqb: 0,
+            zsf3: 0,
+            zsf4: 0,
+            flag: 0,
+        }
+    }
+
+    pub fn read_data(&self, index: u32) -> u32 {
+        match index {
+            0 | 2 | 4 => {
+                let v = self.v[index as usize / 2];
+                pack_halfwords(v[0], v[1])
+            }
+            1 | 3 | 5 => self.v[index as usize / 2][2] as i32 as u32,
+            6 => u32::from_le_bytes(self.rgbc),
+            7 => self.otz as u32,
+            8..=11 => self.ir[index as usize - 8] as i32 as u32,
+            12..=14 => {
+                let [x, y] = self.sxy[index as usize - 12];
+                pack_halfwords(x, y)
+            }
+            15 => pack_halfwords(self.sxy[2][0], self.sxy[2][1]),
+            16..=19 => self.sz[index as usize - 16] as u32,
+            20..=22 => u32::from_le_bytes(self.rgb[index as usize - 20]),
+            23 => self.res1,
+            24..=27 => self.mac[index as usize - 24] as u32,
+            28 | 29 => self.orgb(),
+            30 => self.lzcs,
+            _ => self.lzcr(),
+        }
+    }
+
+    pub fn write_data(&mut self, index: u32, value: u32) {
+        match index {
+            0 | 2 | 4 => {
+                let v = &mut self.v[index as usize / 2];
+                v[0] = value as i16;
+                v[1] = (value >> 16) as i16;
+            }
+            1 | 3 | 5 => self.v[index as usize / 2][2] = value as i16,
+            6 => self.rgbc = value.to_le_bytes(),
+            7 => self.otz = value as u16,
+            8..=11 => self.ir[index as usize - 8] = value as i16,
+            12..=14 => self.sxy[index as usize - 12] = [value as i16, (value >> 16) as i16],
+            15 => {
+                // Writing SXYP pushes a new entry on the FIFO
+                self.sxy[0] = self.sxy[1];
+                self.sxy[1] = self.sxy[2];
+                self.sxy[2] = [value as i16, (value >> 16) as i16];
+            }
+            16..=19 => self.sz[index as usize - 16] = value as u16,
+            20..=22 => self.rgb[index as usize - 20] = value.to_le_bytes(),
+            23 => self.res1 = value,
+            24..=27 => self.mac[index as usize - 24] = value as i32,
+            28 => {
+                // IRGB expands a 15-bit color into IR1 - IR3
+                self.ir[1] = ((value & 0x1f) << 7) as i16;
+                self.ir[2] = (((value >> 5) & 0x1f) << 7) as i16;
+                self.ir[3] = (((value >> 10) & 0x1f) << 7) as i16;
+            }
+            30 => self.lzcs = value,
+            _ => (), // ORGB and LZCR are read-only
+        }
+    }
+
+    pub fn read_control(&self, index: u32) -> u32 {
+        match index {
+            0..=4 => read_matrix(&self.rt, index),
+            5..=7 => self.tr[index as usize - 5] as u32,
+            8..=12 => read_matrix(&self.llm, index - 8),
+            13..=15 => self.bk[index as usize - 13] as u32,
+            16..=20 => read_matrix(&self.lcm, index - 16),
+            21..=23 => self.fc[index as usize - 21] as u32,
+            24 => self.ofx as u32,
+            25 => self.ofy as u32,
+            26 => self.h as i16 as i32 as u32, // H is unsigned, but reads back sign-extended
+            27 => self.dqa as i32 as u32,
+            28 => self.dqb as u32,
+            29 => self.zsf3 as i32 as u32,
+            30 => self.zsf4 as i32 as u32,
+            _ => self.flag,
+        }
+    }
+
+    pub fn write_control(&mut self, index: u32, value: u32) {
+        match index {
+            0..=4 => write_matrix(&mut self.rt, index, value),
+            5..=7 => self.tr[index as usize - 5] = value as i32,
+            8..=12 => write_matrix(&mut self.llm, index - 8, value),
+            13..=15 => self.bk[index as usize - 13] = value as i32,
+            16..=20 => write_matrix(&mut self.lcm, index - 16, value),
+            21..=23 => self.fc[index as usize - 21] = value as i32,
+            24 => self.ofx = value as i32,
+            25 => self.ofy = value as i32,
+            26 => self.h = value as u16,
+            27 => self.dqa = value as i16,
+            28 => self.dqb = value as i32,
+            29 => self.zsf3 = value as i16,
+            30 => self.zsf4 = value as i16,
+            _ => {
+                self.flag = value & FLAG_WRITE_MASK;
+                self.update_error_flag();
+            }
+        }
+    }
+
+    /// Executes a GTE command (the low 25 bits of a COP2 instruction).
+    pub fn command(&mut self, command: u32) {
+        let shift = if command & (1 << 19) != 0 { 12 } else { 0 };
+        let lm = command & (1 << 10) != 0;
+
+        self.flag = 0;
+
+        match command & 0x3f {
+            0x01 => self.rtps(0, shift, lm, true),
+            0x06 => self.nclip(),
+            0x0c => self.op(shift, lm),
+            0x10 => self.dpcs(shift, lm),
+            0x11 => self.intpl(shift, lm),
+            0x12 => self.mvmva(command, shift, lm),
+            0x13 => self.ncds(0, shift, lm),
+            0x14 => self.cdp(shift, lm),
+            0x16 => {
+                for index in 0..3 {
+                    self.ncds(index, shift, lm);
+                }
+            }
+            0x1b => self.nccs(0, shift, lm),
+            0x1c => self.cc(shift, lm),
+            0x1e => self.ncs(0, shift, lm),
+            0x20 => {
+                for index in 0..3 {
+                    self.ncs(index, shift, lm);
+                }
+            }
+            0x28 => self.sqr(shift, lm),
+            0x29 => self.dcpl(shift, lm),
+            0x2a => self.dpct(shift, lm),
+            0x2d => self.avsz3(),
+            0x2e => self.avsz4(),
+            0x30 => {
+                self.rtps(0, shift, lm, false);
+                self.rtps(1, shift, lm, false);
+                self.rtps(2, shift, lm, true);
+            }
+            0x3d => self.gpf(shift, lm),
+            0x3e => self.gpl(shift, lm),
+            0x3f => {
+                for index in 0..3 {
+                    self.nccs(index, shift, lm);
+                }
+            }
+            _ => (), // Unknown commands leave the registers alone
+        }
+
+        self.update_error_flag();
+    }
+
+    /// Perspective transformation of one vector.
+    fn rtps(&mut self, index: usize, shift: u32, lm: bool, last: bool) {
+        let v = self.v[index];
+        let (rt, tr) = (self.rt, self.tr);
+        let mut z = 0;
+
+        for i in 0..3 {
+            let mut value = (tr[i] as i64) << 12;
+            for j in 0..3 {
+                value = self.check_mac(i + 1, value + rt[i][j] as i64 * v[j] as i64);
+            }
+
+            let mac = self.set_mac(i + 1, value, shift);
+
+            if i < 2 {
+                self.set_ir(i + 1, mac, lm);
+            } else {
+                z = value >> 12;
+            }
+        }
+
+        // IR3 saturation is flagged from the Z value shifted by 12 whatever
+        // the value of sf, but IR3 itself is clamped from MAC3 unflagged.
+        if !(-0x8000..=0x7fff).contains(&z) {
+            self.flag |= ir_saturated_flag(3);
+        }
+        let min = if lm { 0 } else { -0x8000 };
+        self.ir[3] = self.mac[3].clamp(min, 0x7fff) as i16;
+
+        self.push_sz(z);
+
+        let n = self.divide() as i64;
+
+        let x = n * self.ir[1] as i64 + self.ofx as i64;
+        let y = n * self.ir[2] as i64 + self.ofy as i64;
+        self.check_mac0(x);
+        self.check_mac0(y);
+        self.push_sxy(x >> 16, y >> 16);
+
+        if last {
+            let depth = n * self.dqa as i64 + self.dqb as i64;
+            self.set_mac0(depth);
+            self.set_ir0(depth >> 12);
+        }
+    }
+
+    /// Divides H by SZ3 the way the hardware does (unsigned Newton-Raphson),
+    /// returning a 1.16 fixed point value.
+    fn divide(&mut self) -> u32 {
+        let h = self.h as u32;
+        let sz3 = self.sz[3] as u32;
+
+        if h >= sz3 * 2 {
+            self.flag |= FLAG_DIVIDE_OVERFLOW;
+            return 0x1ffff;
+        }
+
+        let shift = (sz3 as u16).leading_zeros();
+        let n = (h << shift) as i64;
+        let d = (sz3 << shift) as i64;
+
+        let u = UNR_TABLE[((d - 0x7fc0) >> 7) as usize] as i64 + 0x101;
+        let d = (0x2000080 - d * u) >> 8;
+        let d = (0x0000080 + d * u) >> 8;
+
+        ((n * d + 0x8000) >> 16).min(0x1ffff) as u32
+    }
+
+    /// Normal clipping: the sign of the screen-space triangle area.
+    fn nclip(&mut self) {
+        let [[x0, y0], [x1, y1], [x2, y2]] = self.sxy;
+        let (x0, y0, x1, y1, x2, y2) = (
+            x0 as i64, y0 as i64, x1 as i64, y1 as i64, x2 as i64, y2 as i64,
+        );
+
+        self.set_mac0(x0 * y1 + x1 * y2 + x2 * y0 - x0 * y2 - x1 * y0 - x2 * y1);
+    }
+
+    /// Outer product of the rotation matrix diagonal and IR.
+    fn op(&mut self, shift: u32, lm: bool) {
+        let d = [self.rt[0][0], self.rt[1][1], self.rt[2][2]].map(|d| d as i64);
+        let ir = [self.ir[1], self.ir[2], self.ir[3]].map(|ir| ir as i64);
+
+        self.set_mac_and_ir(1, ir[2] * d[1] - ir[1] * d[2], shift, lm);
+        self.set_mac_and_ir(2, ir[0] * d[2] - ir[2] * d[0], shift, lm);
+        self.set_mac_and_ir(3, ir[1] * d[0] - ir[0] * d[1], shift, lm);
+    }
+
+    /// Multiplies a matrix by a vector, with an optional translation.
+    fn mvmva(&mut self, command: u32, shift: u32, lm: bool) {
+        let matrix = match (command >> 17) & 3 {
+            0 => self.rt,
+            1 => self.llm,
+            2 => self.lcm,
+            _ => {
+                // Selecting the fourth matrix yields garbage
+                let r = (self.rgbc[0] as i16) << 4;
+                [[-r, r, self.ir[0]], [self.rt[0][2]; 3], [self.rt[1][1]; 3]]
+            }
+        };
+
+        let vector = match (command >> 15) & 3 {
+            3 => [self.ir[1], self.ir[2], self.ir[3]],
+            index => self.v[index as usize],
+        };
+
+        match (command >> 13) & 3 {
+            0 => self.multiply_matrix_vector(&matrix, vector, self.tr, shift, lm),
+            1 => self.multiply_matrix_vector(&matrix, vector, self.bk, shift, lm),
+            2 => self.multiply_far_color_bugged(&matrix, vector, shift, lm),
+            _ => self.multiply_matrix_vector(&matrix, vector, [0; 3], shift, lm),
+        }
+    }
+
+    /// The far color translation is broken in MVMVA: the first column only
+    /// contributes to the flags, and the result is computed from the other
+    /// two.
+    fn multiply_far_color_bugged(&mut self, matrix: &Matrix, v: Vector, shift: u32, lm: bool) {
+        for (i, row) in matrix.iter().enumerate() {
+            let discarded = self.check_mac(
+                i + 1,
+                ((self.fc[i] as i64) << 12) + row[0] as i64 * v[0] as i64,
+            );
+            self.set_ir(i + 1, (discarded >> shift) as i32, false);
+
+            let mut value = self.check_mac(i + 1, row[1] as i64 * v[1] as i64);
+            value = self.check_mac(i + 1, value + row[2] as i64 * v[2] as i64);
+
+            self.set_mac_and_ir(i + 1, value, shift, lm);
+        }
+    }
+
+    fn ncs(&mut self, index: usize, shift: u32, lm: bool) {
+        self.light(index, shift, lm);
+        self.push_color();
+    }
+
+    fn nccs(&mut self, index: usize, shift: u32, lm: bool) {
+        self.light(index, shift, lm);
+        self.color_product(shift, lm);
+        self.push_color();
+    }
+
+    fn ncds(&mut self, index: usize, shift: u32, lm: bool) {
+        self.light(index, shift, lm);
+        let color = self.color_ir_product();
+        self.interpolate(color, shift, lm);
+        self.push_color();
+    }
+
+    fn cc(&mut self, shift: u32, lm: bool) {
+        self.light_color(shift, lm);
+        self.color_product(shift, lm);
+        self.push_color();
+    }
+
+    fn cdp(&mut self, shift: u32, lm: bool) {
+        self.light_color(shift, lm);
+        let color = self.color_ir_product();
+        self.interpolate(color, shift, lm);
+        self.push_color();
+    }
+
+    fn dcpl(&mut self, shift: u32, lm: bool) {
+        let color = self.color_ir_product();
+        self.interpolate(color, shift, lm);
+        self.push_color();
+    }
+
+    fn dpcs(&mut self, shift: u32, lm: bool) {
+        let color = self.rgbc;
+        self.interpolate([0, 1, 2].map(|i| (color[i] as i64) << 16), shift, lm);
+        self.push_color();
+    }
+
+    fn dpct(&mut self, shift: u32, lm: bool) {
+        // Always works on the oldest FIFO entry, which moves every iteration
+        for _ in 0..3 {
+            let color = self.rgb[0];
+            self.interpolate([0, 1, 2].map(|i| (color[i] as i64) << 16), shift, lm);
+            self.push_color();
+        }
+    }
+
+    fn intpl(&mut self, shift: u32, lm: bool) {
+        let ir = self.ir;
+        self.interpolate([1, 2, 3].map(|i| (ir[i] as i64) << 12), shift, lm);
+        self.push_color();
+    }
+
+    fn sqr(&mut self, shift: u32, lm: bool) {
+        for i in 1..4 {
+            let ir = self.ir[i] as i64;
+            self.set_mac_and_ir(i, ir * ir, shift, lm);
+        }
+    }
+
+    fn avsz3(&mut self) {
+        let sum = self.sz[1] as i64 + self.sz[2] as i64 + self.sz[3] as i64;
+        let value = self.zsf3 as i64 * sum;
+
+        self.set_mac0(value);
+        self.set_otz(value >> 12);
+    }
+
+    fn avsz4(&mut self) {
+        let sum = self.sz.iter().map(|&z| z as i64).sum::<i64>();
+        let value = self.zsf4 as i64 * sum;
+
+        self.set_mac0(value);
+        self.set_otz(value >> 12);
+    }
+
+    /// General purpose interpolation: IR * IR0.
+    fn gpf(&mut self, shift: u32, lm: bool) {
+        let ir0 = self.ir[0] as i64;
+
+        for i in 1..4 {
+            let ir = self.ir[i] as i64;
+            self.set_mac_and_ir(i, ir * ir0, shift, lm);
+        }
+
+        self.push_color();
+    }
+
+    /// General purpose interpolation with base: MAC + IR * IR0.
+    fn gpl(&mut self, shift: u32, lm: bool) {
+        let ir0 = self.ir[0] as i64;
+
+        for i in 1..4 {
+            let base = (self.mac[i] as i64) << shift;
+            let ir = self.ir[i] as i64;
+            self.set_mac_and_ir(i, base + ir * ir0, shift, lm);
+        }
+
+        self.push_color();
+    }
+
+    /// IR = LLM * V, then IR = BK + LCM * IR.
+    fn light(&mut self, index: usize, shift: u32, lm: bool) {
+        let llm = self.llm;
+        self.multiply_matrix_vector(&llm, self.v[index], [0; 3], shift, lm);
+        self.light_color(shift, lm);
+    }
+
+    /// IR = BK + LCM * IR.
+    fn light_color(&mut self, shift: u32, lm: bool) {
+        let lcm = self.lcm;
+        let ir = [self.ir[1], self.ir[2], self.ir[3]];
+        self.multiply_matrix_vector(&lcm, ir, self.bk, shift, lm);
+    }
+
+    /// [R * IR1, G * IR2, B * IR3] << 4, before any shift.
+    fn color_ir_product(&self) -> [i64; 3] {
+        [0, 1, 2].map(|i| ((self.rgbc[i] as i64) << 4) * self.ir[i + 1] as i64)
+    }
+
+    fn color_product(&mut self, shift: u32, lm: bool) {
+        let color = self.color_ir_product();
+
+        for (i, &value) in color.iter().enumerate() {
+            self.set_mac_and_ir(i + 1, value, shift, lm);
+        }
+    }
+
+    /// Depth cueing: MAC = MAC + (FC - MAC) * IR0.
+    fn interpolate(&mut self, mac: [i64; 3], shift: u32, lm: bool) {
+        for (i, &value) in mac.iter().enumerate() {
+            let far = (self.fc[i] as i64) << 12;
+            self.set_mac_and_ir(i + 1, far - value, shift, false);
+        }
+
+        let ir0 = self.ir[0] as i64;
+
+        for (i, &value) in mac.iter().enumerate() {
+            let ir = self.ir[i + 1] as i64;
+            self.set_mac_and_ir(i + 1, ir * ir0 + value, shift, lm);
+        }
+    }
+
+    fn multiply_matrix_vector(
+        &mut self,
+        matrix: &Matrix,
+        v: Vector,
+        translation: [i32; 3],
+        shift: u32,
+        lm: bool,
+    ) {
+        for i in 0..3 {
+            let mut value = (translation[i] as i64) << 12;
+            for j in 0..3 {
+                value = self.check_mac(i + 1, value + matrix[i][j] as i64 * v[j] as i64);
+            }
+
+            self.set_mac_and_ir(i + 1, value, shift, lm);
+        }
+    }
+
+    /// Flags a MAC1 - MAC3 overflow (44-bit signed accumulators) and returns
+    /// the value truncated to 44 bits.
+    fn check_mac(&mut self, index: usize, value: i64) -> i64 {
+        if value > 0x7ff_ffff_ffff {
+            self.flag |= 1 << (31 - index);
+        } else if value < -0x800_0000_0000 {
+            self.flag |= 1 << (28 - index);
+        }
+
+        (value << 20) >> 20
+    }
+
+    fn set_mac(&mut self, index: usize, value: i64, shift: u32) -> i32 {
+        let value = self.check_mac(index, value) >> shift;
+        self.mac[index] = value as i32;
+        value as i32
+    }
+
+    fn set_ir(&mut self, index: usize, value: i32, lm: bool) {
+        let min = if lm { 0 } else { -0x8000 };
+
+        if value < min || value > 0x7fff {
+            self.flag |= ir_saturated_flag(index);
+        }
+
+        self.ir[index] = value.clamp(min, 0x7fff) as i16;
+    }
+
+    fn set_mac_and_ir(&mut self, index: usize, value: i64, shift: u32, lm: bool) {
+        let mac = self.set_mac(index, value, shift);
+        self.set_ir(index, mac, lm);
+    }
+
+    fn check_mac0(&mut self, value: i64) {
+        if value > i32::MAX as i64 {
+            self.flag |= FLAG_MAC0_POSITIVE;
+        } else if value < i32::MIN as i64 {
+            self.flag |= FLAG_MAC0_NEGATIVE;
+        }
+    }
+
+    fn set_mac0(&mut self, value: i64) {
+        self.check_mac0(value);
+        self.mac[0] = value as i32;
+    }
+
+    fn set_ir0(&mut self, value: i64) {
+        if !(0..=0x1000).contains(&value) {
+            self.flag |= FLAG_IR0_SATURATED;
+        }
+
+        self.ir[0] = value.clamp(0, 0x1000) as i16;
+    }
+
+    fn set_otz(&mut self, value: i64) {
+        if !(0..=0xffff).contains(&value) {
+            self.flag |= FLAG_SZ3_OTZ_SATURATED;
+        }
+
+        self.otz = value.clamp(0, 0xffff) as u16;
+    }
+
+    fn push_sz(&mut self, value: i64) {
+        if !(0..=0xffff).contains(&value) {
+            self.flag |= FLAG_SZ3_OTZ_SATURATED;
+        }
+
+        self.sz[0] = self.sz[1];
+        self.sz[1] = self.sz[2];
+        self.sz[2] = self.sz[3];
+        self.sz[3] = value.clamp(0, 0xffff) as u16;
+    }
+
+    fn push_sxy(&mut self, x: i64, y: i64) {
+        if !(-0x400..=0x3ff).contains(&x) {
+            self.flag |= FLAG_SX2_SATURATED;
+        }
+        if !(-0x400..=0x3ff).contains(&y) {
+            self.flag |= FLAG_SY2_SATURATED;
+        }
+
+        self.sxy[0] = self.sxy[1];
+        self.sxy[1] = self.sxy[2];
+        self.sxy[2] = [x.clamp(-0x400, 0x3ff) as i16, y.clamp(-0x400, 0x3ff) as i16];
+    }
+
+    /// Pushes [MAC1, MAC2, MAC3] / 16 on the color FIFO, keeping the code
+    /// byte of RGBC.
+    fn push_color(&mut self) {
+        let mut color = [0; 4];
+
+        for (i, component) in color.iter_mut().take(3).enumerate() {
+            let value = self.mac[i + 1] >> 4;
+
+            if !(0..=0xff).contains(&value) {
+                self.flag |= 1 << (21 - i);
+            }
+
+            *component = value.clamp(0, 0xff) as u8;
+        }
+        color[3] = self.rgbc[3];
+
+        self.rgb[0] = self.rgb[1];
+        self.rgb[1] = self.rgb[2];
+        self.rgb[2] = color;
+    }
+
+    fn update_error_flag(&mut self) {
+        if self.flag & FLAG_ERROR_MASK != 0 {
+            self.flag |= FLAG_ERROR;
+        } else {
+            self.flag &= !FLAG_ERROR;
+        }
+    }
+
+    /// IR1 - IR3 packed back into a 15-bit color
+    fn orgb(&self) -> u32 {
+        let component = |ir: i16| ((ir >> 7).clamp(0, 0x1f)) as u32;
+
+        component(self.ir[1]) | (component(self.ir[2]) << 5) | (component(self.ir[3]) << 10)
+    }
+
+    /// Number of leading bits of LZCS equal to its sign bit
+    fn lzcr(&self) -> u32 {
+        if (self.lzcs as i32) < 0 {
+            (!self.lzcs).leading_zeros()
+        } else {
+            self.lzcs.leading_zeros()
+        }
+    }
+}
+
+fn ir_saturated_flag(index: usize) -> u32 {
+    1 << (25 - index)
+}
+
+fn pack_halfwords(low: i16, high: i16) -> u32 {
+    (low as u16 as u32) | ((high as u16 as u32) << 16)
+}
+
+/// Matrices are stored as 9 consecutive halfwords, the last one alone in
+/// its register and sign-extended.
+fn read_matrix(matrix: &Matrix, index: u32) -> u32 {
+    let element = |n: usize| matrix[n / 3][n % 3];
+    let n = index as usize * 2;
+
+    if index == 4 {
+        element(8) as i32 as u32
+    } else {
+        pack_halfwords(element(n), element(n + 1))
+    }
+}
+
+fn write_matrix(matrix: &mut Matrix, index: u32, value: u32) {
+    let n = index as usize * 2;
+
+    matrix[n / 3][n % 3] = value as i16;
+
+    if index != 4 {
+        matrix[(n + 1) / 3][(n + 1) % 3] = (value >> 16) as i16;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity() -> Matrix {
+        [[0x1000, 0, 0], [0, 0x1000, 0], [0, 0, 0x1000]]
+    }
+
+    #[test]
+    fn unr_table_matches_hardware() {
+        assert_eq!(&UNR_TABLE[..4], &[0xff, 0xfd, 0xfb, 0xf9]);
+        assert_eq!(UNR_TABLE[0x80], 0x54);
+        assert_eq!(UNR_TABLE[256], 0x00);
+    }
+
+    #[test]
+    fn rtps_projects_a_vertex() {
+        let mut gte = Gte::new();
+        gte.rt = identity();
+        gte.h = 0x200;
+        gte.ofx = 160 << 16;
+        gte.ofy = 120 << 16;
+        gte.dqa = -0x100;
+        gte.dqb = 0x1000000;
+        gte.v[0] = [100, 50, 0x1000];
+
+        gte.command(0x0180001); // rtps, sf=1
+
+        assert_eq!(gte.mac[1..], [100, 50, 0x1000]);
+        assert_eq!(gte.ir[1..], [100, 50, 0x1000]);
+        assert_eq!(gte.sz[3], 0x1000);
+        // 0x200 / 0x1000 = 1/8
+        assert_eq!(gte.sxy[2], [160 + 12, 120 + 6]);
+        assert_eq!(gte.mac[0], 0x2000 * -0x100 + 0x1000000);
+        assert_eq!(gte.ir[0], 0xe00);
+        assert_eq!(gte.flag, 0);
+    }
+
+    #[test]
+    fn rtps_flags_divide_overflow() {
+        let mut gte = Gte::new();
+        gte.rt = identity();
+        gte.h = 0x1000;
+        gte.v[0] = [0, 0, 0x100];
+
+        gte.command(0x0180001);
+
+        assert_eq!(gte.sz[3], 0x100);
+        assert_eq!(gte.flag, FLAG_ERROR | FLAG_DIVIDE_OVERFLOW);
+    }
+
+    #[test]
+    fn rtps_flags_ir3_from_the_unshifted_z() {
+        let mut gte = Gte::new();
+        gte.rt = identity();
+        gte.h = 0;
+        gte.v[0] = [0, 0, 0x10];
+
+        gte.command(0x0000001); // rtps, sf=0
+
+        // MAC3 = 0x10000 saturates IR3, but Z >> 12 = 0x10 does not set
+        // the IR3 flag
+        assert_eq!(gte.mac[3], 0x10000);
+        assert_eq!(gte.ir[3], 0x7fff);
+        assert_eq!(gte.flag & ir_saturated_flag(3), 0);
+    }
+
+    #[test]
+    fn nclip_computes_the_winding() {
+        let mut gte = Gte::new();
+        gte.sxy = [[0, 0], [10, 0], [0, 10]];
+
+        gte.command(0x1400006);
+
+        assert_eq!(gte.mac[0], 100);
+
+        gte.sxy = [[0, 0], [0, 10], [10, 0]];
+        gte.command(0x1400006);
+
+        assert_eq!(gte.mac[0], -100);
+    }
+
+    #[test]
+    fn avsz3_averages_z() {
+        let mut gte = Gte::new();
+        gte.zsf3 = 0x555;
+        gte.sz = [0, 300, 600, 900];
+
+        gte.command(0x158002d);
+
+        assert_eq!(gte.mac[0], 1800 * 0x555);
+        assert_eq!(gte.otz, 599);
+        assert_eq!(gte.flag, 0);
+    }
+
+    #[test]
+    fn avsz4_saturates_otz() {
+        let mut gte = Gte::new();
+        gte.zsf4 = 0x1000;
+        gte.sz = [0xffff, 0xffff, 0, 0];
+
+        gte.command(0x168002e);
+
+        assert_eq!(gte.otz, 0xffff);
+        assert_eq!(gte.flag, FLAG_ERROR | FLAG_SZ3_OTZ_SATURATED);
+    }
+
+    #[test]
+    fn sqr_saturates_ir() {
+        let mut gte = Gte::new();
+        gte.ir = [0, 0x7fff, 2, -3];
+
+        gte.command(0x0a00428); // sqr, sf=0, lm=1
+
+        assert_eq!(gte.mac[1..], [0x3fff0001, 4, 9]);
+        assert_eq!(gte.ir[1..], [0x7fff, 4, 9]);
+        assert_eq!(gte.flag, FLAG_ERROR | ir_saturated_flag(1));
+    }
+
+    #[test]
+    fn mvmva_with_far_color_uses_two_columns() {
+        let mut gte = Gte::new();
+        gte.rt = identity();
+        gte.fc = [1, 2, 3];
+        gte.v[0] = [0x10, 0x20, 0x30];
+
+        gte.command(0x0084012); // rt * v0 + fc, sf=1
+
+        assert_eq!(gte.mac[1..], [0, 0x20, 0x30]);
+    }
+
+    #[test]
+    fn ncds_applies_depth_cueing() {
+        let mut gte = Gte::new();
+        gte.llm = identity();
+        gte.lcm = identity();
+        gte.rgbc = [0x80, 0x80, 0x80, 0x30];
+        gte.fc = [0xff0, 0, 0];
+        gte.ir[0] = 0x800; // halfway to the far color
+        gte.v[0] = [0x1000, 0x800, 0];
+
+        gte.command(0x0e80413);
+
+        // The lit color is RGB * [0x1000, 0x800, 0] = [0x800, 0x400, 0],
+        // then moved halfway towards FC
+        assert_eq!(gte.mac[1..], [0xbf8, 0x200, 0]);
+        assert_eq!(gte.rgb[2], [0xbf, 0x20, 0x00, 0x30]);
+    }
+
+    #[test]
+    fn data_registers_round_trip() {
+        let mut gte = Gte::new();
+
+        gte.write_data(1, 0x8000);
+        assert_eq!(gte.read_data(1), 0xffff8000);
+
+        gte.write_data(28, 0x7fff);
+        assert_eq!(gte.ir[1..], [0xf80, 0xf80, 0xf80]);
+        assert_eq!(gte.read_data(29), 0x7fff);
+
+        gte.write_data(15, 0x00020001);
+        gte.write_data(15, 0x00040003);
+        assert_eq!(gte.read_data(13), 0x00020001);
+        assert_eq!(gte.read_data(15), 0x00040003);
+
+        gte.write_data(30, 0x00ffffff);
+        assert_eq!(gte.read_data(31), 8);
+        gte.write_data(30, 0xff000000);
+        assert_eq!(gte.read_data(31), 8);
+        gte.write_data(30, 0);
+        assert_eq!(gte.read_data(31), 32);
+    }
+
+    #[test]
+    fn control_registers_round_trip() {
+        let mut gte = Gte::new();
+
+        gte.write_control(0, 0x12345678);
+        gte.write_control(4, 0xffff8000);
+        assert_eq!(gte.rt[0][0], 0x5678);
+        assert_eq!(gte.rt[0][1], 0x1234);
+        assert_eq!(gte.read_control(4), 0xffff8000);
+
+        gte.write_control(26, 0x8000);
+        assert_eq!(gte.read_control(26), 0xffff8000);
+
+        gte.write_control(31, 0xffffffff);
+        assert_eq!(gte.read_control(31), 0xfffff000);
+        gte.write_control(31, FLAG_SY2_SATURATED);
+        assert_eq!(gte.read_control(31), FLAG_SY2_SATURATED | FLAG_ERROR);
+        gte.write_control(31, FLAG_IR0_SATURATED);
+        assert_eq!(gte.read_control(31), FLAG_IR0_SATURATED);
+    }
+}
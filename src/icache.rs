@@ -0,0 +1,217 @@
+/// 4 KiB of 16-byte lines, direct mapped
+const LINE_COUNT: usize = 256;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CacheLine {
+    pub tag: u32,        // Physical address bits 12-28
+    pub valid: u8,       // One bit per word
+    pub words: [u32; 4], // Cached instructions
+}
+
+/// Instruction cache, used by fetches from KUSEG and KSEG0
+#[derive(Debug, Clone)]
+pub struct Icache {
+    pub lines: [CacheLine; LINE_COUNT],
+}
+
+impl Icache {
+    pub fn new() -> Self {
+        Self {
+            lines: [CacheLine::default(); LINE_COUNT],
+        }
+    }
+
+    fn line_index(address: u32) -> usize {
+        ((address >> 4) as usize) % LINE_COUNT
+    }
+
+    fn tag(address: u32) -> u32 {
+        (address & 0x1fffffff) >> 12
+    }
+
+    fn word_index(address: u32) -> usize {
+        ((address >> 2) & 0x3) as usize
+    }
+
+    /// Returns the cached instruction at `address`, if any.
+    pub fn lookup(&self, address: u32) -> Option<u32> {
+        let line = &self.lines[Icache::line_index(address)];
+        let word = Icache::word_index(address);
+
+        if line.tag == Icache::tag(address) && line.valid & (1 << word) != 0 {
+            Some(line.words[word])
+        } else {
+            None
+        }
+    }
+
+    /// Refills the line of `address` with `words`, starting at the word
+    /// `address` points to.
+    pub fn fill(&mut self, address: u32, words: &[u32]) {
+        let line = &mut self.lines[Icache::line_index(address)];
+        let tag = Icache::tag(address);
+        let first = Icache::word_index(address);
+
+        if line.tag != tag {
+            line.tag = tag;
+            line.valid = 0;
+        }
+
+        for (index, word) in words.iter().enumerate() {
+            line.words[first + index] = *word;
+            line.valid |= 1 << (first + index);
+        }
+    }
+
+    /// Isolated cache write: replaces the data of a word, leaving its tag and
+    /// valid bit alone.
+    pub fn store(&mut self, address: u32, word: u32) {
+        self.lines[Icache::line_index(address)].words[Icache::word_index(address)] = word;
+    }
+
+    /// Isolated cache write in tag test mode: retags the line and marks all
+    /// of its words invalid.
+    pub fn invalidate(&mut self, address: u32) {
+        let line = &mut self.lines[Icache::line_index(address)];
+
+        line.tag = Icache::tag(address);
+        line.valid = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cop0::SR_ISC, cpu::Cpu, memory::RAM_CAPACITY_RETAIL};
+
+    const PROGRAM_ADDRESS: u32 = 0x80010000;
+
+    /// CPU with the i-cache enabled, about to run `program` from KSEG0.
+    fn cached_cpu(program: &[u32]) -> Cpu {
+        let mut cpu = Cpu::new(RAM_CAPACITY_RETAIL);
+
+        for (index, word) in program.iter().enumerate() {
+            cpu.memory
+                .store32(PROGRAM_ADDRESS + index as u32 * 4, *word)
+                .unwrap();
+        }
+
+        cpu.memory.store32(0xfffe0130, 1 << 11).unwrap();
+        restart(&mut cpu);
+        cpu
+    }
+
+    fn restart(cpu: &mut Cpu) {
+        cpu.pc = PROGRAM_ADDRESS;
+        cpu.next_pc = PROGRAM_ADDRESS + 4;
+    }
+
+    #[test]
+    fn misses_until_filled() {
+        let mut icache = Icache::new();
+
+        assert_eq!(icache.lookup(0x80010004), None);
+
+        icache.fill(0x80010000, &[1, 2, 3, 4]);
+
+        assert_eq!(icache.lookup(0x80010004), Some(2));
+        assert_eq!(icache.lookup(0x8001000c), Some(4));
+        assert_eq!(icache.lookup(0x80010010), None);
+
+        // KUSEG and KSEG0 share physical tags
+        assert_eq!(icache.lookup(0x00010004), Some(2));
+    }
+
+    #[test]
+    fn partial_refills_only_validate_their_words() {
+        let mut icache = Icache::new();
+
+        icache.fill(0x80010008, &[3, 4]);
+
+        assert_eq!(icache.lines[0].valid, 0b1100);
+        assert_eq!(icache.lookup(0x80010000), None);
+        assert_eq!(icache.lookup(0x80010004), None);
+        assert_eq!(icache.lookup(0x80010008), Some(3));
+        assert_eq!(icache.lookup(0x8001000c), Some(4));
+    }
+
+    #[test]
+    fn another_tag_evicts_the_line() {
+        let mut icache = Icache::new();
+
+        icache.fill(0x80010000, &[1, 2, 3, 4]);
+
+        // 4 KiB further, same line
+        assert_eq!(icache.lookup(0x80011000), None);
+
+        icache.fill(0x80011004, &[6, 7, 8]);
+
+        assert_eq!(icache.lines[0].valid, 0b1110);
+        assert_eq!(icache.lookup(0x80011004), Some(6));
+        assert_eq!(icache.lookup(0x80010004), None);
+        assert_eq!(icache.lookup(0x80010000), None);
+    }
+
+    #[test]
+    fn isolated_stores_write_the_cache_instead_of_ram() {
+        let mut cpu = cached_cpu(&[0x24080001]); // addiu $8, $0, 1
+
+        cpu.run_next_instruction(false);
+        cpu.cop0.sr |= SR_ISC;
+        cpu.store32(PROGRAM_ADDRESS, 0x24080003); // addiu $8, $0, 3
+        cpu.cop0.sr &= !SR_ISC;
+
+        assert_eq!(cpu.memory.load32(PROGRAM_ADDRESS).unwrap(), 0x24080001);
+
+        restart(&mut cpu);
+        cpu.run_next_instruction(false);
+
+        assert_eq!(cpu.reg(8), 3);
+    }
+
+    #[test]
+    fn tag_test_stores_invalidate_the_line() {
+        let mut cpu = cached_cpu(&[0, 0, 0, 0]);
+
+        for _ in 0..4 {
+            cpu.run_next_instruction(false);
+        }
+
+        assert_eq!(cpu.icache.lines[0].valid, 0b1111);
+
+        cpu.memory
+            .store32(0xfffe0130, (1 << 11) | (1 << 2))
+            .unwrap();
+        cpu.cop0.sr |= SR_ISC;
+        cpu.store32(PROGRAM_ADDRESS + 8, 0);
+
+        assert_eq!(cpu.icache.lines[0].valid, 0);
+        assert_eq!(cpu.icache.lookup(PROGRAM_ADDRESS), None);
+    }
+
+    #[test]
+    fn rewritten_code_runs_stale_until_flushed() {
+        let mut cpu = cached_cpu(&[0x24080001]); // addiu $8, $0, 1
+
+        cpu.run_next_instruction(false);
+        cpu.store32(PROGRAM_ADDRESS, 0x24080002); // addiu $8, $0, 2
+
+        restart(&mut cpu);
+        cpu.run_next_instruction(false);
+
+        assert_eq!(cpu.reg(8), 1);
+
+        // Flush the line the way the BIOS does
+        cpu.memory
+            .store32(0xfffe0130, (1 << 11) | (1 << 2))
+            .unwrap();
+        cpu.cop0.sr |= SR_ISC;
+        cpu.store32(PROGRAM_ADDRESS, 0);
+        cpu.cop0.sr &= !SR_ISC;
+
+        restart(&mut cpu);
+        cpu.run_next_instruction(false);
+
+        assert_eq!(cpu.reg(8), 2);
+    }
+}
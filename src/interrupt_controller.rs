@@ -0,0 +1,114 @@
+/// Hardware interrupt sources, numbered by their I_STAT / I_MASK bit
+#[derive(Debug, Clone, Copy)]
+pub enum Interrupt {
+    Vblank = 0,
+    Gpu = 1,
+    #[allow(dead_code)] // The CD-ROM drive is not emulated yet
+    Cdrom = 2,
+    Dma = 3,
+    Timer0 = 4,
+    Timer1 = 5,
+    Timer2 = 6,
+    PadMemcard = 7,
+    #[allow(dead_code)] // The serial port is not emulated yet
+    Sio = 8,
+    Spu = 9,
+    #[allow(dead_code)] // Lightguns are not emulated yet
+    Lightpen = 10,
+}
+
+/// Register offsets (0x1f801070 - 0x1f801077)
+const I_STAT: u32 = 0;
+const I_MASK: u32 = 4;
+
+/// Bits of the eleven interrupt sources
+const INTERRUPT_MASK: u32 = 0x7ff;
+
+/// Interrupt controller, drives the CPU's hardware interrupt line (CAUSE bit 10)
+#[derive(Debug, Clone, Default)]
+pub struct InterruptController {
+    pub status: u32, // I_STAT: requested interrupts
+    pub mask: u32,   // I_MASK: enabled interrupts
+}
+
+impl InterruptController {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Latches an interrupt request until it is acknowledged.
+    pub fn request(&mut self, interrupt: Interrupt) {
+        self.status |= 1 << interrupt as u32;
+    }
+
+    /// Whether an enabled interrupt has been requested.
+    pub fn pending(&self) -> bool {
+        self.status & self.mask != 0
+    }
+
+    pub fn load(&self, offset: u32) -> u32 {
+        match offset {
+            I_STAT => self.status,
+            I_MASK => self.mask,
+            _ => 0,
+        }
+    }
+
+    /// Full register value for a narrow store of `value` on the byte lanes
+    /// in `lanes`.
+    pub fn widen(&self, offset: u32, value: u32, lanes: u32) -> u32 {
+        match offset {
+            // Bytes not written leave their interrupts unacknowledged
+            I_STAT => value | !lanes,
+            _ => (self.load(offset) & !lanes) | value,
+        }
+    }
+
+    pub fn store(&mut self, offset: u32, value: u32) {
+        match offset {
+            // Writing zero to a bit acknowledges it, ones are left alone
+            I_STAT => self.status &= value,
+            I_MASK => self.mask = value & INTERRUPT_MASK,
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writing_zeros_acknowledges_requests() {
+        let mut interrupts = InterruptController::new();
+
+        interrupts.request(Interrupt::Vblank);
+        interrupts.request(Interrupt::Dma);
+        interrupts.store(I_STAT, !(1 << Interrupt::Vblank as u32));
+
+        assert_eq!(interrupts.load(I_STAT), 1 << Interrupt::Dma as u32);
+
+        // Ones never set a request
+        interrupts.store(I_STAT, 0xffffffff);
+
+        assert_eq!(interrupts.load(I_STAT), 1 << Interrupt::Dma as u32);
+    }
+
+    #[test]
+    fn mask_keeps_the_eleven_sources_and_gates_pending() {
+        let mut interrupts = InterruptController::new();
+
+        interrupts.request(Interrupt::Spu);
+
+        assert!(!interrupts.pending());
+
+        interrupts.store(I_MASK, 0xffffffff);
+
+        assert_eq!(interrupts.load(I_MASK), 0x7ff);
+        assert!(interrupts.pending());
+
+        interrupts.store(I_MASK, 1 << Interrupt::Vblank as u32);
+
+        assert!(!interrupts.pending());
+    }
+}
@@ -0,0 +1,342 @@
+use crate::interrupt_controller::{Interrupt, InterruptController};
+
+/// Register offsets (0x1f801040 - 0x1f80104f)
+const JOY_DATA: u32 = 0x0;
+const JOY_STAT: u32 = 0x4;
+const JOY_MODE: u32 = 0x8;
+const JOY_CTRL: u32 = 0xa;
+const JOY_BAUD: u32 = 0xe;
+
+/// JOY_CTRL bits
+const CTRL_DTR: u32 = 1 << 1; // Selects the device in the chosen slot
+const CTRL_ACKNOWLEDGE: u32 = 1 << 4;
+const CTRL_RESET: u32 = 1 << 6;
+const CTRL_ACK_IRQ_ENABLE: u32 = 1 << 12;
+const CTRL_SLOT_2: u32 = 1 << 13;
+
+/// CPU cycles between the end of a byte and the device pulling /ACK low
+const ACK_DELAY: u32 = 338;
+
+/// Digital pad buttons, numbered by their bit in the button state. Only the
+/// desktop frontend presses them, headless builds leave them unused.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(not(feature = "window"), allow(dead_code))]
+pub enum Button {
+    Select = 0,
+    Start = 3,
+    Up = 4,
+    Right = 5,
+    Down = 6,
+    Left = 7,
+    L2 = 8,
+    R2 = 9,
+    L1 = 10,
+    R1 = 11,
+    Triangle = 12,
+    Circle = 13,
+    Cross = 14,
+    Square = 15,
+}
+
+/// Progress of a poll of the digital pad (SCPH-1080)
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PadState {
+    Idle,
+    Command,     // Addressed, waiting for 0x42 (read buttons)
+    IdHigh,      // Sent the low ID byte (0x41)
+    ButtonsLow,  // Sent the high ID byte (0x5a)
+    ButtonsHigh, // Sent buttons 0-7
+}
+
+/// Digital pad plugged into slot 1
+#[derive(Debug, Clone)]
+pub struct Pad {
+    pub buttons: u16, // Active low
+    state: PadState,
+}
+
+impl Pad {
+    fn new() -> Self {
+        Self {
+            buttons: 0xffff,
+            state: PadState::Idle,
+        }
+    }
+
+    #[cfg_attr(not(feature = "window"), allow(dead_code))]
+    pub fn set_button(&mut self, button: Button, pressed: bool) {
+        let bit = 1 << button as u16;
+
+        if pressed {
+            self.buttons &= !bit;
+        } else {
+            self.buttons |= bit;
+        }
+    }
+
+    /// Answers a byte sent by the console, and whether /ACK follows (more
+    /// bytes are expected).
+    fn exchange(&mut self, byte: u8) -> (u8, bool) {
+        let (response, next) = match (self.state, byte) {
+            (PadState::Idle, 0x01) => (0xff, PadState::Command),
+            (PadState::Command, 0x42) => (0x41, PadState::IdHigh),
+            (PadState::IdHigh, _) => (0x5a, PadState::ButtonsLow),
+            (PadState::ButtonsLow, _) => (self.buttons as u8, PadState::ButtonsHigh),
+            (PadState::ButtonsHigh, _) => ((self.buttons >> 8) as u8, PadState::Idle),
+            _ => (0xff, PadState::Idle),
+        };
+
+        self.state = next;
+
+        (response, next != PadState::Idle)
+    }
+}
+
+/// Controller and memory card port (SIO0)
+#[derive(Debug, Clone)]
+pub struct Joypad {
+    pub pad: Pad,
+    pub mode: u32,
+    pub ctrl: u32,
+    pub baud: u32,
+    rx: Option<u8>,         // Received byte
+    irq: bool,              // JOY_STAT bit 9
+    transfer: Option<u32>,  // Cycles until the byte being sent is through
+    response: (u8, bool),   // Byte received by the end of the transfer, /ACK
+    ack_delay: Option<u32>, // Cycles until /ACK is pulled low
+    ack: bool,              // /ACK is low (JOY_STAT bit 7)
+}
+
+impl Joypad {
+    pub fn new() -> Self {
+        Self {
+            pad: Pad::new(),
+            mode: 0,
+            ctrl: 0,
+            baud: 0,
+            rx: None,
+            irq: false,
+            transfer: None,
+            response: (0xff, false),
+            ack_delay: None,
+            ack: false,
+        }
+    }
+
+    pub fn tick(&mut self, cycles: u32, interrupts: &mut InterruptController) {
+        if let Some(remaining) = self.transfer {
+            if remaining > cycles {
+                self.transfer = Some(remaining - cycles);
+            } else {
+                let (byte, ack) = self.response;
+
+                self.transfer = None;
+                self.rx = Some(byte);
+
+                // Counted from the end of the byte, part way through `cycles`
+                if ack {
+                    self.ack_delay = Some(ACK_DELAY + remaining);
+                }
+            }
+        }
+
+        if let Some(remaining) = self.ack_delay {
+            if remaining > cycles {
+                self.ack_delay = Some(remaining - cycles);
+            } else {
+                self.ack_delay = None;
+                self.ack = true;
+
+                if self.ctrl & CTRL_ACK_IRQ_ENABLE != 0 && !self.irq {
+                    self.irq = true;
+                    interrupts.request(Interrupt::PadMemcard);
+                }
+            }
+        }
+    }
+
+    fn send(&mut self, byte: u8) {
+        let selected = self.ctrl & CTRL_DTR != 0 && self.ctrl & CTRL_SLOT_2 == 0;
+
+        self.response = if selected {
+            self.pad.exchange(byte)
+        } else {
+            (0xff, false)
+        };
+
+        self.ack = false;
+
+        // 8 bits at the baud rate
+        self.transfer = Some(self.baud.max(1) * 8);
+    }
+
+    fn status(&self) -> u32 {
+        let mut status = 0x1 | 0x4; // TX ready
+
+        status |= (self.rx.is_some() as u32) << 1;
+        status |= (self.ack as u32) << 7;
+        status |= (self.irq as u32) << 9;
+
+        status
+    }
+
+    pub fn load(&mut self, offset: u32) -> u32 {
+        match offset {
+            JOY_DATA => self.rx.take().unwrap_or(0xff) as u32,
+            JOY_STAT => self.status(),
+            JOY_MODE => self.mode,
+            JOY_CTRL => self.ctrl,
+            JOY_BAUD => self.baud,
+            _ => 0,
+        }
+    }
+
+    pub fn store(&mut self, offset: u32, value: u32) {
+        match offset {
+            JOY_DATA => self.send(value as u8),
+            JOY_MODE => self.mode = value & 0xffff,
+            JOY_CTRL => {
+                if value & (CTRL_ACKNOWLEDGE | CTRL_RESET) != 0 {
+                    self.irq = false;
+                }
+
+                if value & CTRL_RESET != 0 {
+                    self.mode = 0;
+                    self.baud = 0;
+                    self.rx = None;
+                    self.transfer = None;
+                    self.ack_delay = None;
+                    self.ack = false;
+                }
+
+                // Deselecting the pad ends the poll
+                if value & CTRL_DTR == 0 {
+                    self.pad.state = PadState::Idle;
+                }
+
+                self.ctrl = value & !(CTRL_ACKNOWLEDGE | CTRL_RESET) & 0xffff;
+            }
+            JOY_BAUD => self.baud = value & 0xffff,
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BAUD: u32 = 0x88;
+
+    /// Joypad with the pad in slot 1 selected and /ACK interrupts enabled
+    fn selected() -> Joypad {
+        let mut joypad = Joypad::new();
+
+        joypad.store(JOY_BAUD, BAUD);
+        joypad.store(JOY_CTRL, CTRL_DTR | CTRL_ACK_IRQ_ENABLE);
+        joypad
+    }
+
+    fn pad_interrupt(interrupts: &mut InterruptController) -> bool {
+        let requested = interrupts.status & (1 << Interrupt::PadMemcard as u32) != 0;
+
+        interrupts.status = 0;
+        requested
+    }
+
+    /// Sends `byte` and waits long enough for /ACK, returns the byte
+    /// received and whether the pad acknowledged it. Acknowledges the
+    /// interrupt.
+    fn exchange(joypad: &mut Joypad, interrupts: &mut InterruptController, byte: u8) -> (u8, bool) {
+        joypad.store(JOY_DATA, byte as u32);
+        joypad.tick(BAUD * 8 + ACK_DELAY, interrupts);
+
+        let ack = pad_interrupt(interrupts);
+        let received = joypad.load(JOY_DATA) as u8;
+
+        assert_eq!(joypad.load(JOY_STAT) & (1 << 7) != 0, ack);
+        assert_eq!(joypad.load(JOY_STAT) & (1 << 9) != 0, ack);
+
+        joypad.store(JOY_CTRL, joypad.ctrl | CTRL_ACKNOWLEDGE);
+
+        assert_eq!(joypad.load(JOY_STAT) & (1 << 9), 0);
+
+        (received, ack)
+    }
+
+    #[test]
+    fn pad_answers_a_poll_with_its_id_and_buttons() {
+        let mut joypad = selected();
+        let mut interrupts = InterruptController::new();
+
+        joypad.pad.set_button(Button::Cross, true);
+        joypad.pad.set_button(Button::Start, true);
+
+        assert_eq!(exchange(&mut joypad, &mut interrupts, 0x01), (0xff, true));
+        assert_eq!(exchange(&mut joypad, &mut interrupts, 0x42), (0x41, true));
+        assert_eq!(exchange(&mut joypad, &mut interrupts, 0x00), (0x5a, true));
+        assert_eq!(exchange(&mut joypad, &mut interrupts, 0x00), (0xf7, true));
+
+        // No /ACK after the last byte
+        assert_eq!(exchange(&mut joypad, &mut interrupts, 0x00), (0xbf, false));
+    }
+
+    #[test]
+    fn ack_follows_the_received_byte() {
+        let mut joypad = selected();
+        let mut interrupts = InterruptController::new();
+
+        joypad.store(JOY_DATA, 0x01);
+        joypad.tick(BAUD * 8 - 1, &mut interrupts);
+
+        assert_eq!(joypad.load(JOY_STAT) & (1 << 1), 0);
+
+        joypad.tick(1, &mut interrupts);
+
+        assert_ne!(joypad.load(JOY_STAT) & (1 << 1), 0);
+
+        joypad.tick(ACK_DELAY - 1, &mut interrupts);
+
+        assert_eq!(joypad.load(JOY_STAT) & (1 << 7), 0);
+        assert!(!pad_interrupt(&mut interrupts));
+
+        joypad.tick(1, &mut interrupts);
+
+        assert_ne!(joypad.load(JOY_STAT) & (1 << 7), 0);
+        assert!(pad_interrupt(&mut interrupts));
+
+        // The next byte releases /ACK
+        joypad.store(JOY_DATA, 0x42);
+
+        assert_eq!(joypad.load(JOY_STAT) & (1 << 7), 0);
+    }
+
+    #[test]
+    fn polls_need_the_pad_selected_and_a_read_command() {
+        let mut interrupts = InterruptController::new();
+
+        // Slot 2 is empty
+        let mut joypad = selected();
+
+        joypad.store(JOY_CTRL, CTRL_DTR | CTRL_ACK_IRQ_ENABLE | CTRL_SLOT_2);
+
+        assert_eq!(exchange(&mut joypad, &mut interrupts, 0x01), (0xff, false));
+
+        // Memory card address, not a pad
+        let mut joypad = selected();
+
+        assert_eq!(exchange(&mut joypad, &mut interrupts, 0x81), (0xff, false));
+
+        // Unknown command
+        assert_eq!(exchange(&mut joypad, &mut interrupts, 0x01), (0xff, true));
+        assert_eq!(exchange(&mut joypad, &mut interrupts, 0x43), (0xff, false));
+
+        // Deselecting aborts the poll
+        assert_eq!(exchange(&mut joypad, &mut interrupts, 0x01), (0xff, true));
+
+        joypad.store(JOY_CTRL, CTRL_ACK_IRQ_ENABLE);
+        joypad.store(JOY_CTRL, CTRL_DTR | CTRL_ACK_IRQ_ENABLE);
+
+        assert_eq!(exchange(&mut joypad, &mut interrupts, 0x42), (0xff, false));
+    }
+}
@@ -1,16 +1,6 @@
 use std::{error::Error, process};
 
 
-pub fn handle_result<T: Default, E: Error>(result: Result<T, E>, message: Option<&'static str>) -> T {
-    match result {
-        Ok(val) => val,
-        Err(err) => {
-            log_error(message, err);
-            T::default()
-        }
-    }
-}
-
 pub fn handle_critical_result<T: Default, E: Error>(result: Result<T, E>, message: Option<&'static str>) -> T {
     match result {
         Ok(val) => val,
@@ -1,19 +1,45 @@
+use audio::{SampleSink, WavSink};
+#[cfg(feature = "audio")]
+use audio_output::LiveAudio;
 use cpu::Cpu;
 use emulator_args::parse_emulator_args;
+use frame_dump::dump_frame;
+#[cfg(feature = "window")]
+use window::Window;
 
 use bios::Bios;
-use logger::handle_critical_result;
+use logger::{handle_critical_result, log_error};
+use memory::{RAM_CAPACITY_DEV_KIT, RAM_CAPACITY_RETAIL};
 
+mod audio;
+#[cfg(feature = "audio")]
+mod audio_output;
 mod bios;
+mod cop0;
 mod cpu;
 mod cpu_instructions;
 mod decoded_instruction;
+mod dma;
 mod emulator_args;
+mod frame_dump;
 mod generic_error;
+mod gpu;
+mod gpu_rasterizer;
+mod gte;
+mod icache;
+mod interrupt_controller;
+mod joypad;
 mod logger;
+mod memlcontrol;
 mod memory;
 mod memory_region;
-mod memlcontrol;
+mod spu;
+mod spu_reverb;
+mod spu_voice;
+mod timers;
+mod video_timing;
+#[cfg(feature = "window")]
+mod window;
 
 /// The entry point of the program
 fn main() {
@@ -22,11 +48,95 @@ fn main() {
     let result = Bios::new(&args.bios);
     let bios = handle_critical_result(result, Some("Failed to load bios:"));
 
-    let mut cpu = Cpu::new();
+    let ram_capacity = if args.dev_kit_ram {
+        RAM_CAPACITY_DEV_KIT
+    } else {
+        RAM_CAPACITY_RETAIL
+    };
+    let mut cpu = Cpu::new(ram_capacity);
 
     cpu.load_bios(bios);
 
+    #[cfg(feature = "window")]
+    let mut window = match Window::new() {
+        Ok(window) => window,
+        Err(err) => {
+            log_error(Some("Failed to open window:"), err);
+            std::process::exit(-1);
+        }
+    };
+
+    let mut sinks: Vec<Box<dyn SampleSink>> = Vec::new();
+
+    if let Some(path) = &args.dump_audio {
+        match WavSink::new(path) {
+            Ok(sink) => sinks.push(Box::new(sink)),
+            Err(err) => {
+                log_error(Some("Failed to create audio dump:"), err);
+                std::process::exit(-1);
+            }
+        }
+    }
+
+    #[cfg(feature = "audio")]
+    let live_audio = match LiveAudio::new() {
+        Ok(live_audio) => {
+            sinks.push(Box::new(live_audio.sink()));
+            Some(live_audio)
+        }
+        Err(err) => {
+            log_error(
+                Some("Failed to open audio output, continuing without sound:"),
+                err,
+            );
+            None
+        }
+    };
+
+    let mut frame = 0;
+
     loop {
-        cpu.run_next_instruction(args.debug);
+        cpu.run_frame(args.debug);
+        frame += 1;
+
+        #[cfg(feature = "window")]
+        {
+            if !window.is_open() {
+                break;
+            }
+
+            if let Err(err) = window.present(&cpu.memory.gpu.display_frame()) {
+                log_error(Some("Failed to present frame:"), err);
+            }
+
+            window.update_pad(&mut cpu.memory.joypad.pad);
+            window.throttle(cpu.memory.gpu.pal());
+        }
+
+        let samples: Vec<(i16, i16)> = cpu.memory.spu.output.drain(..).collect();
+
+        for sink in sinks.iter_mut() {
+            if let Err(err) = sink.write_samples(&samples) {
+                log_error(Some("Failed to write samples:"), err);
+            }
+        }
+
+        #[cfg(feature = "audio")]
+        if let Some(live_audio) = &live_audio {
+            live_audio.wait_for_room();
+        }
+
+        if (args.dump_frames || args.dump_vram) && frame % args.dump_every == 0 {
+            let gpu = &cpu.memory.gpu;
+            let result = dump_frame(&args.dump_dir, frame, gpu, args.dump_frames, args.dump_vram);
+
+            if let Err(err) = result {
+                log_error(Some("Failed to dump frame:"), err);
+            }
+        }
+
+        if args.frames == Some(frame) {
+            break;
+        }
     }
 }
@@ -1,38 +1,241 @@
-use crate::generic_error::GenericError;
+use crate::memory_region::AccessWidth;
 
-pub struct Memlcontrol {}
+/// Memory control registers (0x1f801000 - 0x1f801023)
+pub const EXPANSION_1_BASE: usize = 0;
+pub const EXPANSION_2_BASE: usize = 1;
+pub const EXPANSION_1_DELAY_SIZE: usize = 2;
+pub const EXPANSION_3_DELAY_SIZE: usize = 3;
+pub const BIOS_ROM_DELAY_SIZE: usize = 4;
+pub const SPU_DELAY_SIZE: usize = 5;
+pub const CDROM_DELAY_SIZE: usize = 6;
+pub const EXPANSION_2_DELAY_SIZE: usize = 7;
+pub const COM_DELAY: usize = 8;
+
+/// Values the BIOS programs on boot
+const DEFAULT_REGISTERS: [u32; 9] = [
+    0x1f000000, 0x1f802000, 0x0013243f, 0x00003022, 0x0013243f, 0x200931e1, 0x00020843,
+    0x00070777, 0x00031125,
+];
+
+/// Decoded delay/size register. The hold (COM1) and address auto-increment
+/// bits do not affect timing and are left out.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DelaySize {
+    pub write_delay: u32, // Cycles
+    pub read_delay: u32,  // Cycles
+    pub recovery: bool,   // Uses COM0
+    pub floating: bool,   // Uses COM2
+    pub pre_strobe: bool, // Uses COM3
+    pub bus_16bit: bool,  // 16-bit data bus, 8-bit otherwise
+    pub size: u32,        // Window size in bytes
+}
+
+/// Decoded COM_DELAY register, in cycles
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ComDelay {
+    pub com0: u32,
+    pub com2: u32,
+    pub com3: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct Memlcontrol {
+    pub registers: [u32; 9],
+}
 
 impl Memlcontrol {
-    pub fn store_32(offset: u32, word: u32) -> Option<Result<(), GenericError>> {
-        match offset {
-            0 => {
-                if word != 0x1f000000 {
-                    return Some(Err(GenericError {
-                        message: format!("STORE32_BAD_EXPANSION_1_BASE_ADDRESS (0x{:x})", word),
-                    }));
-                }
-            }
-            4 => {
-                if word != 0x1f802000 {
-                    return Some(Err(GenericError {
-                        message: format!("STORE32_BAD_EXPANSION_2_BASE_ADDRESS (0x{:x})", word),
-                    }));
-                }
+    pub fn new() -> Self {
+        Self {
+            registers: DEFAULT_REGISTERS,
+        }
+    }
+
+    pub fn store_32(&mut self, offset: u32, word: u32) {
+        let index = (offset >> 2) as usize;
+
+        self.registers[index] = match index {
+            // Only the low 24 bits of the base addresses are writable
+            EXPANSION_1_BASE | EXPANSION_2_BASE => 0x1f000000 | (word & 0x00ffffff),
+            EXPANSION_1_DELAY_SIZE..=EXPANSION_2_DELAY_SIZE => word & 0xaf1fffff,
+            _ => word & 0x0003ffff,
+        };
+    }
+
+    pub fn read_32(&self, offset: u32) -> u32 {
+        self.registers[(offset >> 2) as usize]
+    }
+
+    /// Decodes one of the delay/size registers (`EXPANSION_1_DELAY_SIZE` to
+    /// `EXPANSION_2_DELAY_SIZE`).
+    pub fn delay_size(&self, index: usize) -> DelaySize {
+        let value = self.registers[index];
+
+        DelaySize {
+            write_delay: (value & 0xf) + 1,
+            read_delay: ((value >> 4) & 0xf) + 1,
+            recovery: value & (1 << 8) != 0,
+            floating: value & (1 << 10) != 0,
+            pre_strobe: value & (1 << 11) != 0,
+            bus_16bit: value & (1 << 12) != 0,
+            size: 1 << ((value >> 16) & 0x1f),
+        }
+    }
+
+    /// Cycles taken by an access to the region of a delay/size register. Wide
+    /// accesses are split into several transfers on 8 or 16-bit buses, the
+    /// first one paying the full delay.
+    pub fn access_cycles(&self, index: usize, width: AccessWidth, write: bool) -> u32 {
+        let delay = self.delay_size(index);
+        let com = self.com_delay();
+        let access = if write {
+            delay.write_delay
+        } else {
+            delay.read_delay
+        };
+
+        let mut first = 0;
+        let mut sequential = 0;
+        let mut minimum = 0;
+
+        if delay.recovery {
+            first += com.com0.saturating_sub(1);
+            sequential += com.com0.saturating_sub(1);
+        }
+        if delay.floating {
+            first += com.com2;
+            sequential += com.com2;
+        }
+        if delay.pre_strobe {
+            minimum = com.com3;
+        }
+        if first < 6 {
+            first += 1;
+        }
+
+        let first = (first + access + 1).max(minimum + 6);
+        let sequential = (sequential + access + 1).max(minimum + 2);
+
+        match (width, delay.bus_16bit) {
+            (AccessWidth::Byte, _) | (AccessWidth::Halfword, true) => first,
+            (AccessWidth::Halfword, false) | (AccessWidth::Word, true) => first + sequential,
+            (AccessWidth::Word, false) => first + 3 * sequential,
+        }
+    }
+
+    pub fn com_delay(&self) -> ComDelay {
+        let value = self.registers[COM_DELAY];
+
+        ComDelay {
+            com0: value & 0xf,
+            com2: (value >> 8) & 0xf,
+            com3: (value >> 12) & 0xf,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn boot_values_decode() {
+        let memlcontrol = Memlcontrol::new();
+
+        // 0x0013243f, for both expansion region 1 and the BIOS ROM
+        let rom = DelaySize {
+            write_delay: 16,
+            read_delay: 4,
+            recovery: false,
+            floating: true,
+            pre_strobe: false,
+            bus_16bit: false,
+            size: 512 * 1024,
+        };
+
+        assert_eq!(memlcontrol.delay_size(EXPANSION_1_DELAY_SIZE), rom);
+        assert_eq!(memlcontrol.delay_size(BIOS_ROM_DELAY_SIZE), rom);
+
+        // 0x200931e1
+        assert_eq!(
+            memlcontrol.delay_size(SPU_DELAY_SIZE),
+            DelaySize {
+                write_delay: 2,
+                read_delay: 15,
+                recovery: true,
+                floating: false,
+                pre_strobe: false,
+                bus_16bit: true,
+                size: 512,
             }
-            _ => {
-                return Some(Err(GenericError {
-                    message: format!("STORE32_UNHANDLED_MEMLCONTROL_WRITE (0x{:x})", word),
-                }));
+        );
+
+        // 0x00031125
+        assert_eq!(
+            memlcontrol.com_delay(),
+            ComDelay {
+                com0: 5,
+                com2: 1,
+                com3: 1,
             }
-        }
-        None
+        );
+    }
+
+    #[test]
+    fn access_cycles_follow_the_delays_and_bus_width() {
+        let mut memlcontrol = Memlcontrol::new();
+
+        // BIOS ROM: 8-bit bus, COM2 added to every transfer
+        assert_eq!(
+            memlcontrol.access_cycles(BIOS_ROM_DELAY_SIZE, AccessWidth::Byte, false),
+            7
+        );
+        assert_eq!(
+            memlcontrol.access_cycles(BIOS_ROM_DELAY_SIZE, AccessWidth::Halfword, false),
+            13
+        );
+        assert_eq!(
+            memlcontrol.access_cycles(BIOS_ROM_DELAY_SIZE, AccessWidth::Word, false),
+            25
+        );
+        assert_eq!(
+            memlcontrol.access_cycles(BIOS_ROM_DELAY_SIZE, AccessWidth::Byte, true),
+            19
+        );
+
+        // SPU: 16-bit bus, COM0 recovery
+        assert_eq!(
+            memlcontrol.access_cycles(SPU_DELAY_SIZE, AccessWidth::Halfword, false),
+            21
+        );
+        assert_eq!(
+            memlcontrol.access_cycles(SPU_DELAY_SIZE, AccessWidth::Word, false),
+            41
+        );
+
+        // CD-ROM: COM3 pre-strobe sets the minimum
+        memlcontrol.store_32(COM_DELAY as u32 * 4, 0x00009000);
+
+        assert_eq!(
+            memlcontrol.access_cycles(CDROM_DELAY_SIZE, AccessWidth::Byte, false),
+            15
+        );
     }
-    
-    pub fn read_32(offset: u32) -> u32 {
-        match offset {
-            0 => 0x1f000000,
-            4 => 0x1f802000,
-            _ => 0,
+
+    #[test]
+    fn stores_mask_read_only_and_reserved_bits() {
+        let mut memlcontrol = Memlcontrol::new();
+
+        for offset in (0..0x24).step_by(4) {
+            memlcontrol.store_32(offset, 0xffffffff);
+        }
+
+        assert_eq!(memlcontrol.read_32(0x00), 0x1fffffff);
+        assert_eq!(memlcontrol.read_32(0x04), 0x1fffffff);
+
+        for offset in (0x08..0x20).step_by(4) {
+            assert_eq!(memlcontrol.read_32(offset), 0xaf1fffff);
         }
+
+        assert_eq!(memlcontrol.read_32(0x20), 0x0003ffff);
     }
 }
@@ -1,150 +1,949 @@
 use crate::{
     bios::Bios,
+    dma::{Dma, Port, SyncMode, PORTS},
     generic_error::GenericError,
-    memlcontrol::Memlcontrol,
-    memory_region::{MemoryRegionType, REGIONS},
+    gpu::Gpu,
+    interrupt_controller::{Interrupt, InterruptController},
+    joypad::Joypad,
+    memlcontrol::{
+        Memlcontrol, BIOS_ROM_DELAY_SIZE, CDROM_DELAY_SIZE, EXPANSION_1_DELAY_SIZE,
+        EXPANSION_2_DELAY_SIZE, EXPANSION_3_DELAY_SIZE, SPU_DELAY_SIZE,
+    },
+    memory_region::{
+        cached, physical_address, AccessWidth, MemoryRegionType, BIOS, RAM, REGIONS, SCRATCHPAD,
+    },
+    spu::Spu,
+    timers::Timers,
+    video_timing::VideoTiming,
 };
+use std::convert::TryInto;
+
+/// 64 KiB pages
+const PAGE_SHIFT: u32 = 16;
+const PAGE_MASK: u32 = (1 << PAGE_SHIFT) - 1;
+
+/// Pages covering the first 512 MiB of physical memory, everything above is
+/// handled by the I/O path.
+const PAGE_COUNT: usize = 0x20000000 >> PAGE_SHIFT;
+
+const MIB: u32 = 1024 * 1024;
+
+/// RAM installed on retail consoles
+pub const RAM_CAPACITY_RETAIL: usize = 2 * MIB as usize;
+
+/// RAM installed on DTL-H2000 dev kits
+pub const RAM_CAPACITY_DEV_KIT: usize = 8 * MIB as usize;
+
+// Access costs in CPU cycles of the regions not configured through MEMCTRL
+const RAM_ACCESS_CYCLES: u32 = 5;
+const SCRATCHPAD_ACCESS_CYCLES: u32 = 1;
+const IO_ACCESS_CYCLES: u32 = 2;
+
+/// Decoded CACHE_CONTROL register
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CacheControl {
+    pub value: u32,
+    pub tag_test: bool, // Isolated writes invalidate i-cache lines (bit 2)
+    pub scratchpad_enable: bool, // Bits 3 and 7
+    pub icache_enable: bool, // Bit 11
+}
+
+impl CacheControl {
+    pub fn new(value: u32) -> Self {
+        Self {
+            value,
+            tag_test: value & (1 << 2) != 0,
+            scratchpad_enable: value & (1 << 3) != 0 && value & (1 << 7) != 0,
+            icache_enable: value & (1 << 11) != 0,
+        }
+    }
+}
+
+/// Value the BIOS writes to RAM_SIZE: 2 MiB mirrored across the 8 MiB window
+const RAM_SIZE_DEFAULT: u32 = 0x00000b88;
+
+/// Backing storage of a physical page
+#[derive(Clone, Copy, Debug)]
+enum Page {
+    Ram(u32),   // Offset of the page in RAM
+    Bios(u32),  // Offset of the page in the BIOS
+    Scratchpad, // Scratchpad, followed by hardware registers
+    HighZ,      // Nothing drives the bus, reads float high
+    Locked,     // Accesses raise bus errors
+    Io,
+}
+
+/// Decodes the memory window of RAM_SIZE (bits 9-11) into the size of its
+/// part backed by RAM and of the high-Z part after it. The rest of the 8 MiB
+/// window is locked. RAM smaller than its part of the window is mirrored.
+fn ram_window(ram_size: u32) -> (u32, u32) {
+    match (ram_size >> 9) & 0x7 {
+        0 => (MIB, 0),
+        1 => (4 * MIB, 0),
+        2 => (MIB, MIB),
+        3 => (4 * MIB, 4 * MIB),
+        4 => (2 * MIB, 0),
+        6 => (2 * MIB, 2 * MIB),
+        _ => (8 * MIB, 0),
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct Memory {
-    pub ram: Vec<u8>,                // 2048K (0x00000000, 0x80000000, 0xa0000000)
-    pub expansion_region_1: Vec<u8>, // 8192K (0x1f000000, 0x9f000000, 0xbf000000)
-    pub scratchpad: Vec<u8>,         // 1K (0x1f800000, 0x9f800000, 0xbf800000)
-    pub hardware_registers: Vec<u8>, // 8K (0x1f801000, 0x9f801000, 0xbf801000)
-    pub bios: Bios,                  // 512K (0x1fc00000, 0x9fc00000, 0xbfc00000)
-    pub io_ports: Vec<u8>,           // 512B (0xfffe0000)
-    pub ram_size: Vec<u8>,           // 4B (0x1f801060)
-    pub cache_control: Vec<u8>,      // 4B (0xfffe0130)
+    pub ram: Vec<u8>,                // 2048K, 8192K on dev kits (0x00000000)
+    pub expansion_region_1: Vec<u8>, // 8192K (0x1f000000)
+    pub scratchpad: Vec<u8>,         // 1K (0x1f800000)
+    pub hardware_registers: Vec<u8>, // 8K (0x1f801000)
+    pub bios: Bios,                  // 512K (0x1fc00000)
+    pub memlcontrol: Memlcontrol,    // 36B (0x1f801000)
+    pub joypad: Joypad,              // 16B (0x1f801040)
+    pub ram_size: u32,               // 4B (0x1f801060)
+    pub cache_control: CacheControl, // 4B (0xfffe0130)
+    pub interrupt_controller: InterruptController, // 8B (0x1f801070)
+    pub dma: Dma,                    // 128B (0x1f801080)
+    pub timers: Timers,              // 48B (0x1f801100)
+    pub gpu: Gpu,                    // 8B (0x1f801810)
+    pub spu: Spu,                    // 1K (0x1f801c00)
+    pub video_timing: VideoTiming,
+    pages: Vec<Page>,
 }
 
 impl Memory {
-    pub fn new() -> Self {
-        Self {
-            ram: vec![0; 2048 * 1024],
+    pub fn new(ram_capacity: usize) -> Self {
+        let mut memory = Self {
+            ram: vec![0; ram_capacity],
             expansion_region_1: vec![0; 8192 * 1024],
             scratchpad: vec![0; 1024],
             hardware_registers: vec![0; 8 * 1024],
             bios: Bios::default(),
-            io_ports: vec![0; 512],
-            ram_size: vec![0; 4],
-            cache_control: vec![0; 4],
+            memlcontrol: Memlcontrol::new(),
+            joypad: Joypad::new(),
+            ram_size: RAM_SIZE_DEFAULT,
+            cache_control: CacheControl::default(),
+            interrupt_controller: InterruptController::new(),
+            timers: Timers::new(),
+            dma: Dma::new(),
+            gpu: Gpu::new(),
+            spu: Spu::new(),
+            video_timing: VideoTiming::new(),
+            pages: Memory::build_pages(),
+        };
+
+        memory.map_ram();
+        memory
+    }
+
+    fn build_pages() -> Vec<Page> {
+        (0..PAGE_COUNT as u32)
+            .map(|page| {
+                let address = page << PAGE_SHIFT;
+
+                if let Some(offset) = BIOS.contains(address) {
+                    Page::Bios(offset)
+                } else if SCRATCHPAD.contains(address).is_some() {
+                    Page::Scratchpad
+                } else {
+                    Page::Io
+                }
+            })
+            .collect()
+    }
+
+    /// Maps the RAM window as configured by RAM_SIZE.
+    fn map_ram(&mut self) {
+        let (memory, high_z) = ram_window(self.ram_size);
+        let capacity = self.ram.len() as u32;
+
+        for (index, page) in self.pages[..(RAM.1 >> PAGE_SHIFT) as usize]
+            .iter_mut()
+            .enumerate()
+        {
+            let address = (index as u32) << PAGE_SHIFT;
+
+            *page = if address < memory {
+                Page::Ram(address % capacity)
+            } else if address < memory + high_z {
+                Page::HighZ
+            } else {
+                Page::Locked
+            };
         }
     }
 
-    pub fn load32(&self, address: u32) -> Result<u32, GenericError> {
-        if address % 4 != 0 {
-            return Err(GenericError {
-                message: format!("LOAD32_UNALIGNED_WORD_BOUNDARY (from 0x{:x})", address),
-            });
+    fn page(&self, physical: u32) -> Page {
+        match self.pages.get((physical >> PAGE_SHIFT) as usize) {
+            Some(page) => *page,
+            None => Page::Io,
         }
+    }
 
-        for i in 0..16 {
-            let region = REGIONS[i];
+    // Accesses are expected to be naturally aligned, the CPU raises address
+    // error exceptions before reaching memory.
 
-            match region.contains(address) {
-                Some(offset) => {
-                    return Ok(match region.2 {
-                        MemoryRegionType::RAM => Memory::load_generic(&self.ram, offset),
-                        MemoryRegionType::ExpansionRegion => {
-                            Memory::load_generic(&self.expansion_region_1, offset)
-                        }
-                        MemoryRegionType::Scratchpad => {
-                            Memory::load_generic(&self.scratchpad, offset)
-                        }
-                        MemoryRegionType::HardwareRegisters => {
-                            Memory::load_generic(&self.hardware_registers, offset)
-                        }
-                        MemoryRegionType::BIOS => self.bios.load32(offset),
-                        MemoryRegionType::IOPorts => Memory::load_generic(&self.io_ports, offset),
-                        MemoryRegionType::MemlControl => Memlcontrol::read_32(offset),
-                        MemoryRegionType::RAMSize => Memory::load_generic(&self.ram_size, offset),
-                        MemoryRegionType::CacheControl => {
-                            Memory::load_generic(&self.cache_control, offset)
-                        }
-                    })
+    pub fn load32(&mut self, address: u32) -> Result<u32, GenericError> {
+        self.load(address, AccessWidth::Word)
+    }
+
+    pub fn load16(&mut self, address: u32) -> Result<u16, GenericError> {
+        Ok(self.load(address, AccessWidth::Halfword)? as u16)
+    }
+
+    pub fn load8(&mut self, address: u32) -> Result<u8, GenericError> {
+        Ok(self.load(address, AccessWidth::Byte)? as u8)
+    }
+
+    pub fn store32(&mut self, address: u32, word: u32) -> Result<(), GenericError> {
+        self.store(address, AccessWidth::Word, word)
+    }
+
+    pub fn store16(&mut self, address: u32, halfword: u16) -> Result<(), GenericError> {
+        self.store(address, AccessWidth::Halfword, halfword as u32)
+    }
+
+    pub fn store8(&mut self, address: u32, byte: u8) -> Result<(), GenericError> {
+        self.store(address, AccessWidth::Byte, byte as u32)
+    }
+
+    /// Stores the bytes of `word` selected by `mask` (0xff per byte lane), as
+    /// the widest aligned accesses covering them. The other bytes are neither
+    /// read nor written, so I/O registers see no load side effects.
+    pub fn store32_masked(
+        &mut self,
+        address: u32,
+        word: u32,
+        mask: u32,
+    ) -> Result<(), GenericError> {
+        if mask == 0xffffffff {
+            return self.store32(address, word);
+        }
+
+        let mut lane = 0;
+
+        while lane < 4 {
+            let shift = lane * 8;
+
+            if lane % 2 == 0 && (mask >> shift) & 0xffff == 0xffff {
+                self.store16(address + lane, (word >> shift) as u16)?;
+                lane += 2;
+            } else {
+                if (mask >> shift) & 0xff != 0 {
+                    self.store8(address + lane, (word >> shift) as u8)?;
                 }
-                None => continue,
+
+                lane += 1;
             }
         }
 
-        Err(GenericError {
-            message: format!("LOAD32_PERIPHERAL_NOT_FOUND"),
-        })
+        Ok(())
     }
 
-    pub fn store32(&mut self, address: u32, word: u32) -> Result<(), GenericError> {
-        if address % 4 != 0 {
-            return Err(GenericError {
-                message: format!("STORE32_UNALIGNED_WORD_BOUNDARY (into 0x{:x})", address),
-            });
+    /// Lets the peripherals catch up with `cycles` CPU cycles.
+    pub fn tick(&mut self, cycles: u32) {
+        self.video_timing.tick(
+            cycles,
+            &mut self.gpu,
+            &mut self.timers,
+            &mut self.interrupt_controller,
+        );
+        self.timers.tick(cycles, &mut self.interrupt_controller);
+        self.joypad.tick(cycles, &mut self.interrupt_controller);
+        self.spu.tick(cycles, &mut self.interrupt_controller);
+    }
+
+    /// Cycles taken by an access to `address`.
+    pub fn access_cycles(&self, address: u32, width: AccessWidth, write: bool) -> u32 {
+        let physical = match physical_address(address) {
+            Some(physical) => physical,
+            None => return IO_ACCESS_CYCLES,
+        };
+
+        let delay_size = match (self.page(physical), physical) {
+            (Page::Ram(_), _) => return RAM_ACCESS_CYCLES,
+            (Page::Scratchpad, _) if physical & PAGE_MASK < SCRATCHPAD.1 => {
+                return SCRATCHPAD_ACCESS_CYCLES
+            }
+            (Page::Bios(_), _) => BIOS_ROM_DELAY_SIZE,
+            (_, 0x1f000000..=0x1f7fffff) => EXPANSION_1_DELAY_SIZE,
+            (_, 0x1f801800..=0x1f80180f) => CDROM_DELAY_SIZE,
+            (_, 0x1f801c00..=0x1f801fff) => SPU_DELAY_SIZE,
+            (_, 0x1f802000..=0x1f803fff) => EXPANSION_2_DELAY_SIZE,
+            (_, 0x1fa00000..=0x1fbfffff) => EXPANSION_3_DELAY_SIZE,
+            _ => return IO_ACCESS_CYCLES,
+        };
+
+        self.memlcontrol.access_cycles(delay_size, width, write)
+    }
+
+    fn load(&mut self, address: u32, width: AccessWidth) -> Result<u32, GenericError> {
+        let physical = physical_address(address).ok_or_else(|| GenericError {
+            message: format!("LOAD{}_KSEG2_ACCESS (from 0x{:x})", width.bits(), address),
+        })?;
+        let offset = physical & PAGE_MASK;
+
+        match self.page(physical) {
+            Page::Ram(base) => Ok(Memory::load_generic(&self.ram, base + offset, width)),
+            Page::Bios(base) => Ok(Memory::load_generic(&self.bios.data, base + offset, width)),
+            Page::Scratchpad if offset < SCRATCHPAD.1 => {
+                if !cached(address) {
+                    return Err(GenericError {
+                        message: format!(
+                            "LOAD{}_UNCACHED_SCRATCHPAD (from 0x{:x})",
+                            width.bits(),
+                            address
+                        ),
+                    });
+                }
+
+                // A disabled scratchpad leaves the bus floating
+                if self.cache_control.scratchpad_enable {
+                    Ok(Memory::load_generic(&self.scratchpad, offset, width))
+                } else {
+                    Ok(width.mask())
+                }
+            }
+            Page::HighZ => Ok(width.mask()),
+            Page::Locked => Err(GenericError {
+                message: format!("LOAD{}_LOCKED_RAM (from 0x{:x})", width.bits(), address),
+            }),
+            _ => self.load_io(physical, width),
         }
+    }
 
-        for i in 0..REGIONS.len() {
-            let region = REGIONS[i];
+    fn store(&mut self, address: u32, width: AccessWidth, value: u32) -> Result<(), GenericError> {
+        let physical = physical_address(address).ok_or_else(|| GenericError {
+            message: format!("STORE{}_KSEG2_ACCESS (into 0x{:x})", width.bits(), address),
+        })?;
+        let offset = physical & PAGE_MASK;
 
-            match region.contains(address) {
-                Some(offset) => {
-                    return Ok(match region.2 {
-                        MemoryRegionType::RAM => Memory::store_generic(&mut self.ram, offset, word),
-                        MemoryRegionType::ExpansionRegion => {
-                            Memory::store_generic(&mut self.expansion_region_1, offset, word)
-                        }
-                        MemoryRegionType::Scratchpad => {
-                            Memory::store_generic(&mut self.scratchpad, offset, word)
-                        }
-                        MemoryRegionType::HardwareRegisters => {
-                            Memory::store_generic(&mut self.hardware_registers, offset, word)
-                        }
-                        MemoryRegionType::BIOS => (), // BIOS is read-only
-                        MemoryRegionType::IOPorts => {
-                            Memory::store_generic(&mut self.io_ports, offset, word)
-                        }
-                        MemoryRegionType::MemlControl => {
-                            if let Some(value) = Memlcontrol::store_32(offset, word) {
-                                return value;
-                            }
-                        }
-                        MemoryRegionType::RAMSize => {
-                            Memory::store_generic(&mut self.ram_size, offset, word)
-                        }
-                        MemoryRegionType::CacheControl => {
-                            Memory::store_generic(&mut self.cache_control, offset, word)
-                        }
+        match self.page(physical) {
+            Page::Ram(base) => Memory::store_generic(&mut self.ram, base + offset, width, value),
+            Page::Bios(_) => (), // BIOS is read-only
+            Page::Scratchpad if offset < SCRATCHPAD.1 => {
+                if !cached(address) {
+                    return Err(GenericError {
+                        message: format!(
+                            "STORE{}_UNCACHED_SCRATCHPAD (into 0x{:x})",
+                            width.bits(),
+                            address
+                        ),
                     });
                 }
-                None => continue,
+
+                if self.cache_control.scratchpad_enable {
+                    Memory::store_generic(&mut self.scratchpad, offset, width, value)
+                }
+            }
+            Page::HighZ => (),
+            Page::Locked => {
+                return Err(GenericError {
+                    message: format!("STORE{}_LOCKED_RAM (into 0x{:x})", width.bits(), address),
+                })
+            }
+            _ => return self.store_io(physical, width, value),
+        }
+
+        Ok(())
+    }
+
+    /// Slow path, for hardware registers and anything outside of RAM, BIOS
+    /// and scratchpad.
+    fn load_io(&mut self, physical: u32, width: AccessWidth) -> Result<u32, GenericError> {
+        for region in REGIONS.iter() {
+            if let Some(offset) = region.contains(physical) {
+                let region_type = region.2;
+
+                let bus_width = region_type.bus_width();
+
+                return Ok(if width < bus_width {
+                    let aligned = offset & !(bus_width as u32 - 1);
+                    let value = self.load_region(region_type, aligned, bus_width);
+                    (value >> ((offset - aligned) * 8)) & width.mask()
+                } else {
+                    self.load_region(region_type, offset, width)
+                });
+            }
+        }
+
+        Err(GenericError {
+            message: format!(
+                "LOAD{}_PERIPHERAL_NOT_FOUND (from 0x{:x})",
+                width.bits(),
+                physical
+            ),
+        })
+    }
+
+    fn store_io(
+        &mut self,
+        physical: u32,
+        width: AccessWidth,
+        value: u32,
+    ) -> Result<(), GenericError> {
+        for region in REGIONS.iter() {
+            if let Some(offset) = region.contains(physical) {
+                let region_type = region.2;
+
+                let bus_width = region_type.bus_width();
+
+                return if width < bus_width {
+                    let aligned = offset & !(bus_width as u32 - 1);
+                    let shift = (offset - aligned) * 8;
+                    let lanes = width.mask() << shift;
+                    let value = value << shift;
+
+                    let value = match region_type {
+                        MemoryRegionType::InterruptControl => {
+                            self.interrupt_controller.widen(aligned, value, lanes)
+                        }
+                        MemoryRegionType::Dma => self.dma.widen(aligned, value, lanes),
+                        _ => value,
+                    };
+
+                    self.store_region(region_type, aligned, bus_width, value)
+                } else {
+                    self.store_region(region_type, offset, width, value)
+                };
             }
         }
 
         Err(GenericError {
-            message: format!("STORE32_PERIPHERAL_NOT_FOUND"),
+            message: format!(
+                "STORE{}_PERIPHERAL_NOT_FOUND (into 0x{:x})",
+                width.bits(),
+                physical
+            ),
         })
     }
 
+    /// Expansion region 1 only responds within the window size set in its
+    /// delay/size register, the bus floats past it.
+    fn expansion_1_selected(&self, offset: u32) -> bool {
+        offset < self.memlcontrol.delay_size(EXPANSION_1_DELAY_SIZE).size
+    }
+
+    fn load_region(
+        &mut self,
+        region_type: MemoryRegionType,
+        offset: u32,
+        width: AccessWidth,
+    ) -> u32 {
+        match region_type {
+            MemoryRegionType::Ram => Memory::load_generic(&self.ram, offset, width),
+            MemoryRegionType::ExpansionRegion if self.expansion_1_selected(offset) => {
+                Memory::load_generic(&self.expansion_region_1, offset, width)
+            }
+            MemoryRegionType::ExpansionRegion => width.mask(),
+            MemoryRegionType::Scratchpad => Memory::load_generic(&self.scratchpad, offset, width),
+            MemoryRegionType::HardwareRegisters => {
+                Memory::load_generic(&self.hardware_registers, offset, width)
+            }
+            MemoryRegionType::Bios => match width {
+                AccessWidth::Byte => self.bios.load8(offset) as u32,
+                AccessWidth::Halfword => self.bios.load16(offset) as u32,
+                AccessWidth::Word => self.bios.load32(offset),
+            },
+            MemoryRegionType::MemlControl => self.memlcontrol.read_32(offset),
+            MemoryRegionType::Joypad => self.joypad.load(offset),
+            MemoryRegionType::RAMSize => self.ram_size,
+            MemoryRegionType::CacheControl => self.cache_control.value,
+            MemoryRegionType::InterruptControl => self.interrupt_controller.load(offset),
+            MemoryRegionType::Timers => self.timers.load(offset),
+            MemoryRegionType::Dma => self.dma.load(offset),
+            MemoryRegionType::Gpu => match offset {
+                0 => self.gpu.read(),
+                _ => self.gpu.status(),
+            },
+            MemoryRegionType::Spu => match width {
+                // 16-bit registers, words read two of them
+                AccessWidth::Word => {
+                    self.spu.load(offset) as u32 | (self.spu.load(offset + 2) as u32) << 16
+                }
+                _ => self.spu.load(offset) as u32,
+            },
+        }
+    }
+
+    fn store_region(
+        &mut self,
+        region_type: MemoryRegionType,
+        offset: u32,
+        width: AccessWidth,
+        value: u32,
+    ) -> Result<(), GenericError> {
+        match region_type {
+            MemoryRegionType::Ram => Memory::store_generic(&mut self.ram, offset, width, value),
+            MemoryRegionType::ExpansionRegion => {
+                if self.expansion_1_selected(offset) {
+                    Memory::store_generic(&mut self.expansion_region_1, offset, width, value)
+                }
+            }
+            MemoryRegionType::Scratchpad => {
+                Memory::store_generic(&mut self.scratchpad, offset, width, value)
+            }
+            MemoryRegionType::HardwareRegisters => {
+                Memory::store_generic(&mut self.hardware_registers, offset, width, value)
+            }
+            MemoryRegionType::Bios => (), // BIOS is read-only
+            MemoryRegionType::MemlControl => self.memlcontrol.store_32(offset, value),
+            MemoryRegionType::Joypad => self.joypad.store(offset, value),
+            MemoryRegionType::RAMSize => {
+                self.ram_size = value;
+                self.map_ram();
+            }
+            MemoryRegionType::CacheControl => self.cache_control = CacheControl::new(value),
+            MemoryRegionType::InterruptControl => self.interrupt_controller.store(offset, value),
+            MemoryRegionType::Timers => self.timers.store(offset, value),
+            MemoryRegionType::Dma => {
+                if self.dma.store(offset, value) {
+                    self.interrupt_controller.request(Interrupt::Dma);
+                }
+
+                if let Some(index) = self.dma.active_channel() {
+                    self.run_dma(index);
+                }
+            }
+            MemoryRegionType::Gpu => match offset {
+                0 => self.gpu.gp0(value, &mut self.interrupt_controller),
+                _ => self.gpu.gp1(value),
+            },
+            MemoryRegionType::Spu => {
+                let interrupts = &mut self.interrupt_controller;
+
+                self.spu.store(offset, value as u16, interrupts);
+
+                if width == AccessWidth::Word {
+                    self.spu.store(offset + 2, (value >> 16) as u16, interrupts);
+                }
+            }
+        };
+
+        Ok(())
+    }
+
     pub fn load_bios(&mut self, bios: Bios) {
         self.bios = bios;
     }
 
-    pub fn load_generic(data: &Vec<u8>, offset: u32) -> u32 {
+    /// Carries out a whole DMA transfer at once.
+    fn run_dma(&mut self, index: usize) {
+        match self.dma.channels[index].sync_mode() {
+            SyncMode::LinkedList => self.dma_linked_list(index),
+            _ => self.dma_block(index),
+        }
+
+        if self.dma.finish(index) {
+            self.interrupt_controller.request(Interrupt::Dma);
+        }
+    }
+
+    fn dma_block(&mut self, index: usize) {
+        let channel = self.dma.channels[index];
+        let port = PORTS[index];
+        let mask = self.ram.len() as u32 - 1;
+        let mut address = channel.madr;
+
+        for remaining in (0..channel.words()).rev() {
+            let offset = address & mask & !3;
+
+            if channel.reads_ram() {
+                let word = Memory::load_generic(&self.ram, offset, AccessWidth::Word);
+                self.dma_write_port(port, word);
+            } else {
+                let word = self.dma_read_port(port, address, remaining);
+                Memory::store_generic(&mut self.ram, offset, AccessWidth::Word, word);
+            }
+
+            address = address.wrapping_add(channel.step());
+        }
+
+        // Block transfers leave MADR past the data and BCR counted down
+        if channel.sync_mode() == SyncMode::Block {
+            let channel = &mut self.dma.channels[index];
+
+            channel.madr = address & 0xffffff;
+            channel.bcr &= 0xffff;
+        }
+    }
+
+    /// Sends a list of packets, each one headed by a word holding its size
+    /// (bits 24-31) and the address of the next one (bits 0-23).
+    fn dma_linked_list(&mut self, index: usize) {
+        let port = PORTS[index];
+        let mask = self.ram.len() as u32 - 1;
+        let mut address = self.dma.channels[index].madr & mask & !3;
+
+        // Lists that loop on themselves would hang the hardware, give up
+        // after visiting as many nodes as RAM can hold.
+        for _ in 0..self.ram.len() / 4 {
+            let header = Memory::load_generic(&self.ram, address, AccessWidth::Word);
+
+            for word in 1..=(header >> 24) {
+                let offset = address.wrapping_add(word * 4) & mask;
+                let word = Memory::load_generic(&self.ram, offset, AccessWidth::Word);
+                self.dma_write_port(port, word);
+            }
+
+            // End of list marker (usually 0xffffff)
+            if header & 0x800000 != 0 {
+                break;
+            }
+
+            address = header & mask & !3;
+        }
+
+        self.dma.channels[index].madr = 0xffffff;
+    }
+
+    /// Word handed by a peripheral to a transfer into RAM.
+    fn dma_read_port(&mut self, port: Port, address: u32, remaining: u32) -> u32 {
+        match port {
+            // Each ordering table entry links to the previous one
+            Port::Otc if remaining == 0 => 0xffffff,
+            Port::Otc => address.wrapping_sub(4) & 0x1fffff,
+            Port::Gpu => self.gpu.read(),
+            Port::Spu => self.spu.dma_read(&mut self.interrupt_controller),
+            _ => 0, // Not connected yet
+        }
+    }
+
+    /// Word sent to a peripheral by a transfer from RAM.
+    fn dma_write_port(&mut self, port: Port, word: u32) {
+        match port {
+            Port::Gpu => self.gpu.gp0(word, &mut self.interrupt_controller),
+            Port::Spu => self.spu.dma_write(word, &mut self.interrupt_controller),
+            _ => (),
+        }
+    }
+
+    /// Little-endian read of `width` bytes.
+    pub fn load_generic(data: &[u8], offset: u32, width: AccessWidth) -> u32 {
+        let offset = offset as usize;
+
+        match width {
+            AccessWidth::Byte => data[offset] as u32,
+            AccessWidth::Halfword => {
+                u16::from_le_bytes(data[offset..offset + 2].try_into().unwrap()) as u32
+            }
+            AccessWidth::Word => u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap()),
+        }
+    }
+
+    /// Little-endian write of the low `width` bytes of `value`.
+    pub fn store_generic(data: &mut [u8], offset: u32, width: AccessWidth, value: u32) {
         let offset = offset as usize;
-        let b0 = data[offset + 0] as u32;
-        let b1 = data[offset + 1] as u32;
-        let b2 = data[offset + 2] as u32;
-        let b3 = data[offset + 3] as u32;
-        b0 | (b1 << 8) | (b2 << 16) | (b3 << 24)
+        let bytes = value.to_le_bytes();
+
+        data[offset..offset + width as usize].copy_from_slice(&bytes[..width as usize]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Where an access to a physical address ends up
+    #[derive(Debug, PartialEq)]
+    enum Backing {
+        Ram(u32),
+        Bios(u32),
+        Scratchpad(u32),
+        HighZ,
+        Locked,
+        Io,
+    }
+
+    /// Through the page table, as `load` and `store` dispatch.
+    fn paged(memory: &Memory, physical: u32) -> Backing {
+        let offset = physical & PAGE_MASK;
+
+        match memory.page(physical) {
+            Page::Ram(base) => Backing::Ram(base + offset),
+            Page::Bios(base) => Backing::Bios(base + offset),
+            Page::Scratchpad if offset < SCRATCHPAD.1 => Backing::Scratchpad(offset),
+            Page::HighZ => Backing::HighZ,
+            Page::Locked => Backing::Locked,
+            _ => Backing::Io,
+        }
+    }
+
+    /// Through a scan of the regions, as before the page table.
+    fn scanned(memory: &Memory, physical: u32) -> Backing {
+        let found = REGIONS
+            .iter()
+            .find_map(|region| region.contains(physical).map(|offset| (region.2, offset)));
+
+        match found {
+            Some((MemoryRegionType::Ram, offset)) => {
+                let (ram, high_z) = ram_window(memory.ram_size);
+
+                if offset < ram {
+                    Backing::Ram(offset % memory.ram.len() as u32)
+                } else if offset < ram + high_z {
+                    Backing::HighZ
+                } else {
+                    Backing::Locked
+                }
+            }
+            Some((MemoryRegionType::Bios, offset)) => Backing::Bios(offset),
+            Some((MemoryRegionType::Scratchpad, offset)) => Backing::Scratchpad(offset),
+            _ => Backing::Io,
+        }
+    }
+
+    #[test]
+    fn page_table_matches_the_region_scan() {
+        let boundaries = [
+            0x001fffff, 0x00200000, // End of 2 MiB of RAM
+            0x007fffff, 0x00800000, // End of the RAM window
+            0x1effffff, 0x1f000000, // Expansion region 1
+            0x1f8003ff, 0x1f800400, // End of the scratchpad
+            0x1f801000, 0x1f802000, // Hardware registers
+            0x1fbfffff, 0x1fc00000, // Start of the BIOS
+            0x1fc7ffff, 0x1fc80000, // End of the BIOS
+            0x1fffffff, 0x20000000, // End of the page table
+        ];
+        let pages = (0..PAGE_COUNT as u32).flat_map(|page| {
+            let start = page << PAGE_SHIFT;
+            vec![start, start | PAGE_MASK]
+        });
+        let addresses: Vec<u32> = pages.chain(boundaries.iter().copied()).collect();
+
+        for &(capacity, ram_size) in [
+            (RAM_CAPACITY_RETAIL, 0x00000888),       // 2 MiB, the rest locked
+            (RAM_CAPACITY_RETAIL, RAM_SIZE_DEFAULT), // 2 MiB mirrored four times
+            (RAM_CAPACITY_DEV_KIT, 0x00000888),
+            (RAM_CAPACITY_DEV_KIT, RAM_SIZE_DEFAULT),
+        ]
+        .iter()
+        {
+            let mut memory = Memory::new(capacity);
+
+            memory.store32(0x1f801060, ram_size).unwrap();
+
+            for &physical in addresses.iter() {
+                let expected = scanned(&memory, physical);
+
+                // KUSEG, KSEG0 and KSEG1 mirror the first 512 MiB
+                let segments: &[u32] = if physical < 0x20000000 {
+                    &[0x00000000, 0x80000000, 0xa0000000]
+                } else {
+                    &[0x00000000]
+                };
+
+                for &segment in segments {
+                    let mirrored = physical_address(segment | physical).unwrap();
+
+                    assert_eq!(
+                        paged(&memory, mirrored),
+                        expected,
+                        "{:08x}",
+                        segment | physical
+                    );
+                }
+            }
+        }
     }
 
-    pub fn store_generic(data: &mut Vec<u8>, offset: u32, word: u32) {
-        let address = offset as usize;
+    #[test]
+    fn ram_size_selects_mirrored_high_z_and_locked_parts() {
+        // RAM_SIZE bits 9-11, end of the RAM part and end of the high-Z part
+        let windows = [
+            (0, MIB, MIB),
+            (1, 4 * MIB, 4 * MIB),
+            (2, MIB, 2 * MIB),
+            (3, 4 * MIB, 8 * MIB),
+            (4, 2 * MIB, 2 * MIB),
+            (5, 8 * MIB, 8 * MIB),
+            (6, 2 * MIB, 4 * MIB),
+            (7, 8 * MIB, 8 * MIB),
+        ];
+
+        for &(setting, ram_end, high_z_end) in windows.iter() {
+            let mut memory = Memory::new(RAM_CAPACITY_RETAIL);
+
+            memory.store32(0x1f801060, setting << 9).unwrap();
+            memory.store32(0xa0000000, 0x12345678).unwrap();
+
+            // 2 MiB of RAM repeat across the whole RAM part
+            for mirror in (0..ram_end).step_by(2 * MIB as usize) {
+                assert_eq!(memory.load32(0xa0000000 + mirror).unwrap(), 0x12345678);
+            }
+
+            if high_z_end > ram_end {
+                assert_eq!(memory.load32(0xa0000000 + ram_end).unwrap(), 0xffffffff);
+            }
+
+            if high_z_end < 8 * MIB {
+                assert!(memory.load32(0xa0000000 + high_z_end).is_err());
+                assert!(memory.store32(0xa0000000 + high_z_end, 0).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn dev_kit_ram_is_not_mirrored() {
+        let mut memory = Memory::new(RAM_CAPACITY_DEV_KIT);
+
+        memory.store32(0xa0000000, 0x12345678).unwrap();
+        memory.store32(0xa0200000, 0x9abcdef0).unwrap();
+
+        assert_eq!(memory.load32(0xa0000000).unwrap(), 0x12345678);
+        assert_eq!(memory.load32(0xa0200000).unwrap(), 0x9abcdef0);
+        assert_eq!(memory.load32(0xa07ffffc).unwrap(), 0);
+    }
+
+    #[test]
+    fn expansion_1_floats_past_its_window() {
+        let mut memory = Memory::new(RAM_CAPACITY_RETAIL);
+
+        // 64 KiB window
+        memory.store32(0x1f801008, 0x0010243f).unwrap();
+        memory.store8(0x1f00ffff, 0x12).unwrap();
+        memory.store8(0x1f010000, 0x34).unwrap();
+
+        assert_eq!(memory.load8(0x1f00ffff).unwrap(), 0x12);
+        assert_eq!(memory.load8(0x1f010000).unwrap(), 0xff);
+    }
+
+    #[test]
+    fn scratchpad_follows_cache_control() {
+        let mut memory = Memory::new(RAM_CAPACITY_RETAIL);
+
+        // Disabled, the bus floats and writes are lost
+        memory.store32(0x1f800000, 0x12345678).unwrap();
+
+        assert_eq!(memory.load32(0x1f800000).unwrap(), 0xffffffff);
+
+        memory.store32(0xfffe0130, 0x00000088).unwrap();
+        memory.store32(0x1f800000, 0x12345678).unwrap();
+
+        assert_eq!(memory.load32(0x1f800000).unwrap(), 0x12345678);
+        assert_eq!(memory.load32(0x9f800000).unwrap(), 0x12345678);
+    }
+
+    #[test]
+    fn uncached_scratchpad_accesses_are_bus_errors() {
+        let mut memory = Memory::new(RAM_CAPACITY_RETAIL);
+
+        memory.store32(0xfffe0130, 0x00000088).unwrap();
+
+        assert!(memory.load32(0xbf800000).is_err());
+        assert!(memory.load8(0xbf8003ff).is_err());
+        assert!(memory.store16(0xbf800010, 0).is_err());
+    }
+
+    /// DMA channel registers (MADR, BCR, CHCR)
+    fn dma_channel(index: u32) -> u32 {
+        0x1f801080 + index * 0x10
+    }
+
+    #[test]
+    fn dma_otc_links_entries_backwards() {
+        let mut memory = Memory::new(RAM_CAPACITY_RETAIL);
+        let otc = dma_channel(6);
+
+        memory.store32(0x1f8010f0, 0x0f654321).unwrap(); // Enable channel 6
+        memory.store32(otc, 0x1010).unwrap();
+        memory.store32(otc + 4, 4).unwrap();
+        memory.store32(otc + 8, 0x11000000).unwrap();
+
+        assert_eq!(memory.load32(0x1010).unwrap(), 0x100c);
+        assert_eq!(memory.load32(0x100c).unwrap(), 0x1008);
+        assert_eq!(memory.load32(0x1008).unwrap(), 0x1004);
+        assert_eq!(memory.load32(0x1004).unwrap(), 0xffffff);
+        assert_eq!(memory.load32(otc + 8).unwrap() & 0x01000000, 0);
+    }
+
+    #[test]
+    fn dma_block_transfer_feeds_gp0() {
+        let mut memory = Memory::new(RAM_CAPACITY_RETAIL);
+        let gpu = dma_channel(2);
+
+        // Fill 16x1 pixels at (0, 0) in red
+        for (index, word) in [0x020000ff, 0x00000000, 0x00010010].iter().enumerate() {
+            memory.store32(0x2000 + index as u32 * 4, *word).unwrap();
+        }
+
+        memory.store32(0x1f8010f0, 0x07654b21).unwrap(); // Enable channel 2
+        memory.store32(gpu, 0x2000).unwrap();
+        memory.store32(gpu + 4, 0x00010003).unwrap(); // 1 block of 3 words
+        memory.store32(gpu + 8, 0x01000201).unwrap(); // Block mode, from RAM
+
+        assert_eq!(memory.gpu.vram(15, 0), 0x001f);
+        assert_eq!(memory.gpu.vram(16, 0), 0);
+        assert_eq!(memory.load32(gpu).unwrap(), 0x200c);
+        assert_eq!(memory.load32(gpu + 4).unwrap(), 0x0003);
+    }
+
+    #[test]
+    fn dma_linked_list_follows_headers_and_raises_an_interrupt() {
+        let mut memory = Memory::new(RAM_CAPACITY_RETAIL);
+        let gpu = dma_channel(2);
+
+        memory.store32(0x2000, 0x01002010).unwrap(); // 1 word, next at 0x2010
+        memory.store32(0x2004, 0xe3000401).unwrap(); // Drawing area top left (1, 1)
+        memory.store32(0x2010, 0x01ffffff).unwrap(); // 1 word, end of list
+        memory.store32(0x2014, 0xe4007c3f).unwrap(); // Drawing area bottom right (63, 31)
+
+        memory.store32(0x1f8010f0, 0x07654b21).unwrap(); // Enable channel 2
+        memory.store32(0x1f8010f4, 0x00840000).unwrap(); // Channel 2 IRQ enabled
+        memory.store32(gpu, 0x2000).unwrap();
+        memory.store32(gpu + 8, 0x01000401).unwrap(); // Linked list, from RAM
+
+        let gpu = &memory.gpu;
+
+        assert_eq!((gpu.drawing_area_left, gpu.drawing_area_top), (1, 1));
+        assert_eq!((gpu.drawing_area_right, gpu.drawing_area_bottom), (63, 31));
+        assert_ne!(
+            memory.interrupt_controller.status & (1 << Interrupt::Dma as u32),
+            0
+        );
+    }
+
+    #[test]
+    fn byte_acknowledge_leaves_the_other_interrupts_alone() {
+        let mut memory = Memory::new(RAM_CAPACITY_RETAIL);
+
+        memory.interrupt_controller.status = 0x7ff;
+        memory.store8(0x1f801071, 0xfe).unwrap();
+
+        assert_eq!(memory.interrupt_controller.status, 0x6ff);
+    }
+
+    #[test]
+    fn narrow_gpu_stores_reach_the_ports_on_their_lanes() {
+        let mut memory = Memory::new(RAM_CAPACITY_RETAIL);
+
+        // GP1(03): display enable, from the top byte lane
+        memory.store8(0x1f801817, 0x03).unwrap();
+
+        assert!(!memory.gpu.display_disabled);
+
+        // GP1(00): reset, the upper lanes read as zero
+        memory.store16(0x1f801814, 0x0001).unwrap();
+
+        assert!(memory.gpu.display_disabled);
+
+        // GP0(02): fill rectangle, waits for its parameters
+        memory.store16(0x1f801812, 0x0200).unwrap();
+
+        assert!(!memory.gpu.command.is_empty());
+    }
+
+    #[test]
+    fn narrow_dma_stores_keep_the_other_bytes() {
+        let mut memory = Memory::new(RAM_CAPACITY_RETAIL);
+
+        // Channel 2 IRQ enabled and flagged, master enable
+        memory.store32(0x1f8010f4, 0x00840000).unwrap();
+        memory.dma.dicr |= 1 << 26;
+        memory.store8(0x1f8010f4, 0x3f).unwrap();
+
+        assert_eq!(memory.dma.dicr, 0x0484003f);
 
-        let b0 = (word & 0xff) as u8;
-        let b1 = ((word & 0xff00) >> 8) as u8;
-        let b2 = ((word & 0xff0000) >> 16) as u8;
-        let b3 = ((word & 0xff000000) >> 24) as u8;
+        // Acknowledges the flag only
+        memory.store8(0x1f8010f7, 0x04).unwrap();
 
-        data[address + 0] = b0;
-        data[address + 1] = b1;
-        data[address + 2] = b2;
-        data[address + 3] = b3;
+        assert_eq!(memory.dma.dicr, 0x0084003f);
     }
 }
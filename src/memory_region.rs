@@ -3,15 +3,62 @@ pub struct MemoryRegion(pub u32, pub u32, pub MemoryRegionType);
 
 #[derive(Clone, Copy, Debug)]
 pub enum MemoryRegionType {
-    RAM,
+    Ram,
     ExpansionRegion,
     Scratchpad,
     HardwareRegisters,
-    BIOS,
-    IOPorts,
+    Bios,
     MemlControl,
     RAMSize,
-    CacheControl
+    CacheControl,
+    InterruptControl,
+    Timers,
+    Dma,
+    Gpu,
+    Joypad,
+    Spu,
+}
+
+/// Width of a single memory access
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub enum AccessWidth {
+    Byte = 1,
+    Halfword = 2,
+    Word = 4,
+}
+
+impl AccessWidth {
+    pub fn bits(self) -> u32 {
+        self as u32 * 8
+    }
+
+    pub fn mask(self) -> u32 {
+        match self {
+            AccessWidth::Byte => 0xff,
+            AccessWidth::Halfword => 0xffff,
+            AccessWidth::Word => 0xffffffff,
+        }
+    }
+}
+
+impl MemoryRegionType {
+    /// Narrowest access the region decodes on its own. Narrower accesses
+    /// reach it at that width, with the data on its byte lanes and the other
+    /// lanes zero, unless the bus widens them for the register (see
+    /// `Memory::store_io`).
+    pub fn bus_width(self) -> AccessWidth {
+        match self {
+            MemoryRegionType::MemlControl
+            | MemoryRegionType::RAMSize
+            | MemoryRegionType::CacheControl
+            | MemoryRegionType::Dma
+            | MemoryRegionType::Gpu => AccessWidth::Word,
+            MemoryRegionType::InterruptControl
+            | MemoryRegionType::Timers
+            | MemoryRegionType::Spu => AccessWidth::Halfword,
+            _ => AccessWidth::Byte,
+        }
+    }
 }
 
 impl MemoryRegion {
@@ -26,61 +73,124 @@ impl MemoryRegion {
     }
 }
 
-pub const RAM_1: MemoryRegion = MemoryRegion(0x00000000, 0x200000, MemoryRegionType::RAM);
-pub const RAM_2: MemoryRegion = MemoryRegion(0x80000000, 0x200000, MemoryRegionType::RAM);
-pub const RAM_3: MemoryRegion = MemoryRegion(0xa0000000, 0x200000, MemoryRegionType::RAM);
+/// Masks that strip the segment bits of a virtual address, indexed by its top
+/// three bits: KUSEG (2 GiB), KSEG0 (512 MiB), KSEG1 (512 MiB), KSEG2 (1 GiB).
+/// KUSEG and KSEG2 are left untouched, KSEG0 and KSEG1 mirror the first
+/// 512 MiB of physical memory.
+const SEGMENT_MASKS: [u32; 8] = [
+    0xffffffff, 0xffffffff, 0xffffffff, 0xffffffff, // KUSEG
+    0x7fffffff, // KSEG0
+    0x1fffffff, // KSEG1
+    0xffffffff, 0xffffffff, // KSEG2
+];
 
-pub const EXPANSION_REGION_1_1: MemoryRegion =
-    MemoryRegion(0x1f000000, 0x800000, MemoryRegionType::ExpansionRegion);
-pub const EXPANSION_REGION_1_2: MemoryRegion =
-    MemoryRegion(0x9f000000, 0x800000, MemoryRegionType::ExpansionRegion);
-pub const EXPANSION_REGION_1_3: MemoryRegion =
-    MemoryRegion(0xbf000000, 0x800000, MemoryRegionType::ExpansionRegion);
-
-pub const SCRATCHPAD_1: MemoryRegion =
-    MemoryRegion(0x1f800000, 0x400, MemoryRegionType::Scratchpad);
-pub const SCRATCHPAD_2: MemoryRegion =
-    MemoryRegion(0x9f800000, 0x400, MemoryRegionType::Scratchpad);
-pub const SCRATCHPAD_3: MemoryRegion =
-    MemoryRegion(0xbf800000, 0x400, MemoryRegionType::Scratchpad);
-
-pub const HARDWARE_REGISTERS_1: MemoryRegion =
-    MemoryRegion(0x1f801000, 0x2000, MemoryRegionType::HardwareRegisters);
-pub const HARDWARE_REGISTERS_2: MemoryRegion =
-    MemoryRegion(0x9f801000, 0x2000, MemoryRegionType::HardwareRegisters);
-pub const HARDWARE_REGISTERS_3: MemoryRegion =
-    MemoryRegion(0xbf801000, 0x2000, MemoryRegionType::HardwareRegisters);
+/// Translates a virtual address into a physical one. KSEG2 is not mapped on
+/// the PS1, the cache control register being the only thing reachable there.
+pub fn physical_address(address: u32) -> Option<u32> {
+    let physical = address & SEGMENT_MASKS[(address >> 29) as usize];
 
-pub const BIOS_1: MemoryRegion = MemoryRegion(0x1fc00000, 0x80000, MemoryRegionType::BIOS);
-pub const BIOS_2: MemoryRegion = MemoryRegion(0x9fc00000, 0x80000, MemoryRegionType::BIOS);
-pub const BIOS_3: MemoryRegion = MemoryRegion(0xbfc00000, 0x80000, MemoryRegionType::BIOS);
+    if physical >= 0xc0000000 && CACHE_CONTROL.contains(physical).is_none() {
+        None
+    } else {
+        Some(physical)
+    }
+}
+
+/// KUSEG and KSEG0 go through the caches, KSEG1 and KSEG2 do not.
+pub fn cached(address: u32) -> bool {
+    address < 0xa0000000
+}
 
-pub const IO_PORTS: MemoryRegion = MemoryRegion(0xfffe0000, 0x200, MemoryRegionType::IOPorts);
+// Regions in physical address space. Smaller regions come first, so they take
+// precedence over the ones they overlap.
+
+/// 8 MiB RAM window, see `RAM_SIZE`
+pub const RAM: MemoryRegion = MemoryRegion(0x00000000, 0x800000, MemoryRegionType::Ram);
+
+pub const EXPANSION_REGION_1: MemoryRegion =
+    MemoryRegion(0x1f000000, 0x800000, MemoryRegionType::ExpansionRegion);
+
+pub const SCRATCHPAD: MemoryRegion = MemoryRegion(0x1f800000, 0x400, MemoryRegionType::Scratchpad);
 
 pub const MEMLCONTROL: MemoryRegion = MemoryRegion(0x1f801000, 0x24, MemoryRegionType::MemlControl);
 
 pub const RAM_SIZE: MemoryRegion = MemoryRegion(0x1f801060, 0x4, MemoryRegionType::RAMSize);
 
-pub const CACHE_CONTROL: MemoryRegion = MemoryRegion(0xfffe0130, 0x4, MemoryRegionType::CacheControl);
-
-pub const REGIONS: [MemoryRegion; 19] = [
-    RAM_1,
-    RAM_2,
-    RAM_3,
-    EXPANSION_REGION_1_1,
-    EXPANSION_REGION_1_2,
-    EXPANSION_REGION_1_3,
-    SCRATCHPAD_1,
-    SCRATCHPAD_2,
-    SCRATCHPAD_3,
-    HARDWARE_REGISTERS_1,
-    HARDWARE_REGISTERS_2,
-    HARDWARE_REGISTERS_3,
-    BIOS_1,
-    BIOS_2,
-    BIOS_3,
-    IO_PORTS,
+pub const JOYPAD: MemoryRegion = MemoryRegion(0x1f801040, 0x10, MemoryRegionType::Joypad);
+
+pub const INTERRUPT_CONTROL: MemoryRegion =
+    MemoryRegion(0x1f801070, 0x8, MemoryRegionType::InterruptControl);
+
+pub const DMA: MemoryRegion = MemoryRegion(0x1f801080, 0x80, MemoryRegionType::Dma);
+
+pub const TIMERS: MemoryRegion = MemoryRegion(0x1f801100, 0x30, MemoryRegionType::Timers);
+
+pub const GPU: MemoryRegion = MemoryRegion(0x1f801810, 0x8, MemoryRegionType::Gpu);
+
+pub const SPU: MemoryRegion = MemoryRegion(0x1f801c00, 0x400, MemoryRegionType::Spu);
+
+pub const HARDWARE_REGISTERS: MemoryRegion =
+    MemoryRegion(0x1f801000, 0x2000, MemoryRegionType::HardwareRegisters);
+
+pub const BIOS: MemoryRegion = MemoryRegion(0x1fc00000, 0x80000, MemoryRegionType::Bios);
+
+pub const CACHE_CONTROL: MemoryRegion =
+    MemoryRegion(0xfffe0130, 0x4, MemoryRegionType::CacheControl);
+
+pub const REGIONS: [MemoryRegion; 14] = [
+    RAM,
+    EXPANSION_REGION_1,
+    SCRATCHPAD,
     MEMLCONTROL,
     RAM_SIZE,
-    CACHE_CONTROL
+    JOYPAD,
+    INTERRUPT_CONTROL,
+    DMA,
+    TIMERS,
+    GPU,
+    SPU,
+    HARDWARE_REGISTERS,
+    BIOS,
+    CACHE_CONTROL,
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn segments_map_to_physical_addresses() {
+        // (virtual, physical, cached)
+        let table = [
+            (0x00000000, Some(0x00000000), true),  // KUSEG RAM
+            (0x1fc00000, Some(0x1fc00000), true),  // KUSEG BIOS
+            (0x7fffffff, Some(0x7fffffff), true),  // KUSEG end
+            (0x80000000, Some(0x00000000), true),  // KSEG0 RAM
+            (0x9f801070, Some(0x1f801070), true),  // KSEG0 I_STAT
+            (0x9fffffff, Some(0x1fffffff), true),  // KSEG0 end
+            (0xa0000000, Some(0x00000000), false), // KSEG1 RAM
+            (0xbfc00000, Some(0x1fc00000), false), // KSEG1 BIOS
+            (0xbfffffff, Some(0x1fffffff), false), // KSEG1 end
+            (0xc0000000, None, false),             // KSEG2 start
+            (0xfffe012c, None, false),             // Just before cache control
+            (0xfffe0130, Some(0xfffe0130), false), // Cache control
+            (0xfffe0134, None, false),             // Just past cache control
+            (0xffffffff, None, false),             // KSEG2 end
+        ];
+
+        for &(address, physical, is_cached) in table.iter() {
+            assert_eq!(physical_address(address), physical, "{:08x}", address);
+            assert_eq!(cached(address), is_cached, "{:08x}", address);
+        }
+    }
+
+    #[test]
+    fn bus_widths_follow_the_register_sizes() {
+        assert_eq!(MemoryRegionType::Ram.bus_width(), AccessWidth::Byte);
+        assert_eq!(MemoryRegionType::Bios.bus_width(), AccessWidth::Byte);
+        assert_eq!(MemoryRegionType::Timers.bus_width(), AccessWidth::Halfword);
+        assert_eq!(MemoryRegionType::Spu.bus_width(), AccessWidth::Halfword);
+        assert_eq!(MemoryRegionType::Dma.bus_width(), AccessWidth::Word);
+        assert_eq!(MemoryRegionType::Gpu.bus_width(), AccessWidth::Word);
+    }
+}
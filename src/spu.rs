@@ -0,0 +1,572 @@
+use crate::{
+    interrupt_controller::{Interrupt, InterruptController},
+    spu_reverb::Reverb,
+    spu_voice::{Voice, Volume},
+};
+use std::collections::VecDeque;
+
+/// 512 KiB of sound RAM
+pub const SPU_RAM_SIZE: usize = 512 * 1024;
+
+/// CPU cycles per 44.1 kHz sample
+const CYCLES_PER_SAMPLE: u32 = 768;
+
+pub const VOICE_COUNT: usize = 24;
+
+/// Samples kept for the frontend, about a second
+const OUTPUT_CAPACITY: usize = 44100;
+
+/// Register offsets (0x1f801c00 - 0x1f801fff)
+const MAIN_VOLUME_LEFT: u32 = 0x180;
+const MAIN_VOLUME_RIGHT: u32 = 0x182;
+const REVERB_VOLUME_LEFT: u32 = 0x184;
+const REVERB_VOLUME_RIGHT: u32 = 0x186;
+const KEY_ON: u32 = 0x188;
+const KEY_OFF: u32 = 0x18c;
+const PITCH_MODULATION: u32 = 0x190;
+const NOISE: u32 = 0x194;
+const REVERB_ENABLE: u32 = 0x198;
+const ENDX: u32 = 0x19c;
+const REVERB_BASE: u32 = 0x1a2;
+const IRQ_ADDRESS: u32 = 0x1a4;
+const TRANSFER_ADDRESS: u32 = 0x1a6;
+const TRANSFER_FIFO: u32 = 0x1a8;
+const SPUCNT: u32 = 0x1aa;
+const TRANSFER_CONTROL: u32 = 0x1ac;
+const SPUSTAT: u32 = 0x1ae;
+const CD_VOLUME_LEFT: u32 = 0x1b0;
+const CD_VOLUME_RIGHT: u32 = 0x1b2;
+const CURRENT_MAIN_VOLUME_LEFT: u32 = 0x1b8;
+const CURRENT_MAIN_VOLUME_RIGHT: u32 = 0x1ba;
+
+/// Voice register offsets (0x10 bytes per voice)
+const VOICE_VOLUME_LEFT: u32 = 0x0;
+const VOICE_VOLUME_RIGHT: u32 = 0x2;
+const VOICE_PITCH: u32 = 0x4;
+const VOICE_START_ADDRESS: u32 = 0x6;
+const VOICE_ADSR_LOW: u32 = 0x8;
+const VOICE_ADSR_HIGH: u32 = 0xa;
+const VOICE_ADSR_VOLUME: u32 = 0xc;
+const VOICE_REPEAT_ADDRESS: u32 = 0xe;
+
+/// SPUCNT bits
+const CNT_CD_AUDIO: u16 = 1 << 0;
+const CNT_CD_REVERB: u16 = 1 << 2;
+const CNT_IRQ_ENABLE: u16 = 1 << 6;
+const CNT_REVERB_MASTER: u16 = 1 << 7;
+const CNT_UNMUTE: u16 = 1 << 14;
+
+/// Sound RAM transfer mode (SPUCNT bits 4-5)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransferMode {
+    Stop,
+    ManualWrite,
+    DmaWrite,
+    DmaRead,
+}
+
+/// Sound processing unit
+#[derive(Debug, Clone)]
+pub struct Spu {
+    pub ram: Vec<u8>,
+    pub voices: [Voice; VOICE_COUNT],
+    pub main_volume_left: Volume,
+    pub main_volume_right: Volume,
+    pub cd_volume_left: u16,
+    pub cd_volume_right: u16,
+    pub key_on: u32,
+    pub key_off: u32,
+    pub pitch_modulation: u32,
+    pub noise: u32,
+    pub reverb_enable: u32,
+    pub reverb: Reverb,
+    pub irq_address: u16,      // In 8-byte units
+    pub transfer_address: u16, // In 8-byte units
+    pub control: u16,          // SPUCNT
+    pub irq: bool,             // SPUSTAT bit 6
+    pub output: VecDeque<(i16, i16)>,
+    registers: Vec<u16>, // Registers read back as written
+    transfer_position: u32,
+    cd_audio: VecDeque<(i16, i16)>,
+    noise_level: i16,
+    noise_timer: i32,
+    capture_position: u32, // Halfword in the capture buffers
+    cycles: u32,           // CPU cycles towards the next sample
+}
+
+impl Spu {
+    pub fn new() -> Self {
+        Self {
+            ram: vec![0; SPU_RAM_SIZE],
+            voices: [Voice::default(); VOICE_COUNT],
+            main_volume_left: Volume::default(),
+            main_volume_right: Volume::default(),
+            cd_volume_left: 0,
+            cd_volume_right: 0,
+            key_on: 0,
+            key_off: 0,
+            pitch_modulation: 0,
+            noise: 0,
+            reverb_enable: 0,
+            reverb: Reverb::default(),
+            irq_address: 0,
+            transfer_address: 0,
+            control: 0,
+            irq: false,
+            output: VecDeque::with_capacity(OUTPUT_CAPACITY),
+            registers: vec![0; 0x200],
+            transfer_position: 0,
+            cd_audio: VecDeque::new(),
+            noise_level: 0,
+            noise_timer: 0,
+            capture_position: 0,
+            cycles: 0,
+        }
+    }
+
+    pub fn transfer_mode(&self) -> TransferMode {
+        match (self.control >> 4) & 0x3 {
+            0 => TransferMode::Stop,
+            1 => TransferMode::ManualWrite,
+            2 => TransferMode::DmaWrite,
+            _ => TransferMode::DmaRead,
+        }
+    }
+
+    /// Raises the SPU interrupt if `address` (in bytes) is within the 8-byte
+    /// unit of the IRQ address.
+    fn check_irq(&mut self, address: u32, interrupts: &mut InterruptController) {
+        if address >> 3 == self.irq_address as u32 {
+            self.raise_irq(interrupts);
+        }
+    }
+
+    fn raise_irq(&mut self, interrupts: &mut InterruptController) {
+        if self.control & CNT_IRQ_ENABLE != 0 && !self.irq {
+            self.irq = true;
+            interrupts.request(Interrupt::Spu);
+        }
+    }
+
+    fn write_ram(&mut self, address: u32, value: u16, interrupts: &mut InterruptController) {
+        let address = (address & 0x7fffe) as usize;
+
+        self.ram[address..address + 2].copy_from_slice(&value.to_le_bytes());
+        self.check_irq(address as u32, interrupts);
+    }
+
+    fn read_ram(&mut self, address: u32, interrupts: &mut InterruptController) -> u16 {
+        let address = (address & 0x7fffe) as usize;
+
+        self.check_irq(address as u32, interrupts);
+        u16::from_le_bytes([self.ram[address], self.ram[address + 1]])
+    }
+
+    /// DMA channel 4, into sound RAM.
+    pub fn dma_write(&mut self, word: u32, interrupts: &mut InterruptController) {
+        for &half in &[word as u16, (word >> 16) as u16] {
+            self.write_ram(self.transfer_position, half, interrupts);
+            self.transfer_position = (self.transfer_position + 2) & 0x7ffff;
+        }
+    }
+
+    /// DMA channel 4, out of sound RAM.
+    pub fn dma_read(&mut self, interrupts: &mut InterruptController) -> u32 {
+        let low = self.read_ram(self.transfer_position, interrupts);
+        let high = self.read_ram(self.transfer_position + 2, interrupts);
+
+        self.transfer_position = (self.transfer_position + 4) & 0x7ffff;
+        low as u32 | (high as u32) << 16
+    }
+
+    /// Queues a 44.1 kHz stereo sample of CD audio for mixing.
+    #[cfg_attr(not(test), allow(dead_code))] // Fed by the CD-ROM drive, which is not emulated yet
+    pub fn push_cd_audio(&mut self, left: i16, right: i16) {
+        self.cd_audio.push_back((left, right));
+    }
+
+    pub fn tick(&mut self, cycles: u32, interrupts: &mut InterruptController) {
+        self.cycles += cycles;
+
+        while self.cycles >= CYCLES_PER_SAMPLE {
+            self.cycles -= CYCLES_PER_SAMPLE;
+            self.generate_sample(interrupts);
+        }
+    }
+
+    /// Advances the noise generator by one sample.
+    fn tick_noise(&mut self) {
+        let shift = (self.control >> 10) & 0xf;
+        let step = ((self.control >> 8) & 0x3) as i32 + 4;
+        let level = self.noise_level as u16;
+
+        self.noise_timer -= step;
+
+        if self.noise_timer < 0 {
+            let parity = ((level >> 15) ^ (level >> 12) ^ (level >> 11) ^ (level >> 10) ^ 1) & 1;
+
+            self.noise_level = ((level << 1) | parity) as i16;
+            self.noise_timer += 0x20000 >> shift;
+
+            if self.noise_timer < 0 {
+                self.noise_timer += 0x20000 >> shift;
+            }
+        }
+    }
+
+    fn generate_sample(&mut self, interrupts: &mut InterruptController) {
+        let irq_address = self.irq_address as u32 * 8;
+        let mut left = 0;
+        let mut right = 0;
+        let mut reverb_left = 0;
+        let mut reverb_right = 0;
+
+        self.tick_noise();
+
+        for index in 0..VOICE_COUNT {
+            let modulation = if index > 0 && self.pitch_modulation & (1 << index) != 0 {
+                Some(self.voices[index - 1].output)
+            } else {
+                None
+            };
+            let noise = if self.noise & (1 << index) != 0 {
+                Some(self.noise_level)
+            } else {
+                None
+            };
+
+            let voice = &mut self.voices[index];
+            let sample = voice.sample(&self.ram, irq_address, modulation, noise);
+            let voice_left = (sample * voice.volume_left.level()) >> 15;
+            let voice_right = (sample * voice.volume_right.level()) >> 15;
+
+            left += voice_left;
+            right += voice_right;
+
+            if self.reverb_enable & (1 << index) != 0 {
+                reverb_left += voice_left;
+                reverb_right += voice_right;
+            }
+
+            if std::mem::take(&mut voice.irq_hit) {
+                self.raise_irq(interrupts);
+            }
+        }
+
+        let (cd_left, cd_right) = self.cd_audio.pop_front().unwrap_or((0, 0));
+
+        // Capture buffers: CD left and right, voices 1 and 3
+        let capture = self.capture_position * 2;
+        let captured = [
+            cd_left,
+            cd_right,
+            self.voices[1].output,
+            self.voices[3].output,
+        ];
+
+        for (n, &sample) in captured.iter().enumerate() {
+            self.write_ram(n as u32 * 0x400 + capture, sample as u16, interrupts);
+        }
+
+        self.capture_position = (self.capture_position + 1) & 0x1ff;
+
+        if self.control & CNT_CD_AUDIO != 0 {
+            let cd_left = (cd_left as i32 * self.cd_volume_left as i16 as i32) >> 15;
+            let cd_right = (cd_right as i32 * self.cd_volume_right as i16 as i32) >> 15;
+
+            left += cd_left;
+            right += cd_right;
+
+            if self.control & CNT_CD_REVERB != 0 {
+                reverb_left += cd_left;
+                reverb_right += cd_right;
+            }
+        }
+
+        let reverb_master = self.control & CNT_REVERB_MASTER != 0;
+        let (reverb_left, reverb_right) =
+            self.reverb
+                .process(&mut self.ram, (reverb_left, reverb_right), reverb_master);
+
+        self.main_volume_left.tick();
+        self.main_volume_right.tick();
+
+        let left = ((left * self.main_volume_left.level()) >> 15) + reverb_left;
+        let right = ((right * self.main_volume_right.level()) >> 15) + reverb_right;
+
+        let sample = if self.control & CNT_UNMUTE != 0 {
+            (
+                left.clamp(-0x8000, 0x7fff) as i16,
+                right.clamp(-0x8000, 0x7fff) as i16,
+            )
+        } else {
+            (0, 0)
+        };
+
+        if self.output.len() == OUTPUT_CAPACITY {
+            self.output.pop_front();
+        }
+
+        self.output.push_back(sample);
+    }
+
+    fn status(&self) -> u16 {
+        let mode = self.transfer_mode();
+        let mut status = self.control & 0x3f;
+
+        status |= (self.irq as u16) << 6;
+        status |= ((mode == TransferMode::DmaWrite || mode == TransferMode::DmaRead) as u16) << 7;
+        status |= ((mode == TransferMode::DmaWrite) as u16) << 8;
+        status |= ((mode == TransferMode::DmaRead) as u16) << 9;
+        status |= ((self.capture_position >= 0x100) as u16) << 11;
+
+        status
+    }
+
+    /// Voice bit masks are split into two halfword registers.
+    fn set_mask(mask: &mut u32, offset: u32, value: u16) {
+        if offset & 2 == 0 {
+            *mask = (*mask & 0xffff0000) | value as u32;
+        } else {
+            *mask = (*mask & 0xffff) | (value as u32) << 16;
+        }
+    }
+
+    fn mask_half(mask: u32, offset: u32) -> u16 {
+        (mask >> ((offset & 2) * 8)) as u16
+    }
+
+    pub fn load(&mut self, offset: u32) -> u16 {
+        let offset = offset & 0x3fe;
+
+        match offset {
+            0x000..=0x17f => {
+                let voice = &self.voices[(offset >> 4) as usize];
+
+                match offset & 0xf {
+                    VOICE_ADSR_VOLUME => voice.envelope.level as u16,
+                    VOICE_REPEAT_ADDRESS => voice.repeat_address,
+                    _ => self.registers[(offset >> 1) as usize],
+                }
+            }
+            ENDX | 0x19e => {
+                let endx = self
+                    .voices
+                    .iter()
+                    .enumerate()
+                    .fold(0, |endx, (n, voice)| endx | (voice.end as u32) << n);
+
+                Spu::mask_half(endx, offset)
+            }
+            SPUCNT => self.control,
+            SPUSTAT => self.status(),
+            CURRENT_MAIN_VOLUME_LEFT => self.main_volume_left.level() as u16,
+            CURRENT_MAIN_VOLUME_RIGHT => self.main_volume_right.level() as u16,
+            0x1c0..=0x1ff => self.reverb.registers[((offset - 0x1c0) >> 1) as usize],
+            0x200..=0x25f => {
+                let voice = &self.voices[((offset - 0x200) >> 2) as usize];
+
+                match offset & 2 {
+                    0 => voice.volume_left.level() as u16,
+                    _ => voice.volume_right.level() as u16,
+                }
+            }
+            _ => self.registers[(offset >> 1) as usize],
+        }
+    }
+
+    pub fn store(&mut self, offset: u32, value: u16, interrupts: &mut InterruptController) {
+        let offset = offset & 0x3fe;
+
+        self.registers[(offset >> 1) as usize] = value;
+
+        match offset {
+            0x000..=0x17f => self.store_voice((offset >> 4) as usize, offset & 0xf, value),
+            MAIN_VOLUME_LEFT => self.main_volume_left.set(value),
+            MAIN_VOLUME_RIGHT => self.main_volume_right.set(value),
+            REVERB_VOLUME_LEFT => self.reverb.volume_left = value,
+            REVERB_VOLUME_RIGHT => self.reverb.volume_right = value,
+            0x188 | 0x18a => {
+                Spu::set_mask(&mut self.key_on, offset - KEY_ON, value);
+
+                let irq_address = self.irq_address as u32 * 8;
+
+                for n in 0..16 {
+                    if value & (1 << n) != 0 {
+                        let index = n + (offset - KEY_ON) as usize * 8;
+
+                        if index < VOICE_COUNT {
+                            self.voices[index].key_on(&self.ram, irq_address);
+                        }
+                    }
+                }
+            }
+            0x18c | 0x18e => {
+                Spu::set_mask(&mut self.key_off, offset - KEY_OFF, value);
+
+                for n in 0..16 {
+                    if value & (1 << n) != 0 {
+                        let index = n + (offset - KEY_OFF) as usize * 8;
+
+                        if index < VOICE_COUNT {
+                            self.voices[index].key_off();
+                        }
+                    }
+                }
+            }
+            0x190 | 0x192 => {
+                Spu::set_mask(&mut self.pitch_modulation, offset - PITCH_MODULATION, value)
+            }
+            0x194 | 0x196 => Spu::set_mask(&mut self.noise, offset - NOISE, value),
+            0x198 | 0x19a => Spu::set_mask(&mut self.reverb_enable, offset - REVERB_ENABLE, value),
+            REVERB_BASE => self.reverb.set_base(value),
+            IRQ_ADDRESS => self.irq_address = value,
+            TRANSFER_ADDRESS => {
+                self.transfer_address = value;
+                self.transfer_position = value as u32 * 8;
+            }
+            TRANSFER_FIFO => {
+                self.write_ram(self.transfer_position, value, interrupts);
+                self.transfer_position = (self.transfer_position + 2) & 0x7ffff;
+            }
+            SPUCNT => {
+                self.control = value;
+
+                // Clearing the enable bit acknowledges the interrupt
+                if value & CNT_IRQ_ENABLE == 0 {
+                    self.irq = false;
+                }
+            }
+            TRANSFER_CONTROL => (),
+            CD_VOLUME_LEFT => self.cd_volume_left = value,
+            CD_VOLUME_RIGHT => self.cd_volume_right = value,
+            0x1c0..=0x1ff => self.reverb.registers[((offset - 0x1c0) >> 1) as usize] = value,
+            _ => (),
+        }
+    }
+
+    fn store_voice(&mut self, index: usize, register: u32, value: u16) {
+        let voice = &mut self.voices[index];
+
+        match register {
+            VOICE_VOLUME_LEFT => voice.volume_left.set(value),
+            VOICE_VOLUME_RIGHT => voice.volume_right.set(value),
+            VOICE_PITCH => voice.pitch = value,
+            VOICE_START_ADDRESS => voice.start_address = value,
+            VOICE_ADSR_LOW => voice.adsr = (voice.adsr & 0xffff0000) | value as u32,
+            VOICE_ADSR_HIGH => voice.adsr = (voice.adsr & 0xffff) | (value as u32) << 16,
+            VOICE_ADSR_VOLUME => voice.envelope.level = value as i16,
+            VOICE_REPEAT_ADDRESS => voice.repeat_address = value,
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spu_voice::AdsrPhase;
+
+    /// One ADPCM block of constant samples (shift 0, no filter), flagged
+    /// as the end of a one-shot sample.
+    fn write_block(spu: &mut Spu, address: u32, interrupts: &mut InterruptController) {
+        spu.store(TRANSFER_ADDRESS, (address / 8) as u16, interrupts);
+        spu.store(TRANSFER_FIFO, 0x0100, interrupts); // Flags: loop end
+
+        for _ in 0..7 {
+            spu.store(TRANSFER_FIFO, 0x1111, interrupts);
+        }
+    }
+
+    #[test]
+    fn voice_plays_and_reports_the_end() {
+        let mut interrupts = InterruptController::new();
+        let mut spu = Spu::new();
+
+        write_block(&mut spu, 0x1000, &mut interrupts);
+        spu.store(SPUCNT, 0xc000, &mut interrupts);
+        spu.store(MAIN_VOLUME_LEFT, 0x3fff, &mut interrupts);
+        spu.store(MAIN_VOLUME_RIGHT, 0x3fff, &mut interrupts);
+        spu.store(VOICE_VOLUME_LEFT, 0x3fff, &mut interrupts);
+        spu.store(VOICE_VOLUME_RIGHT, 0x3fff, &mut interrupts);
+        spu.store(VOICE_PITCH, 0x1000, &mut interrupts); // 44.1 kHz
+        spu.store(VOICE_START_ADDRESS, 0x1000 / 8, &mut interrupts);
+        spu.store(VOICE_ADSR_LOW, 0x00ff, &mut interrupts); // Fastest attack
+        spu.store(KEY_ON, 0x1, &mut interrupts);
+
+        spu.tick(CYCLES_PER_SAMPLE * 10, &mut interrupts);
+
+        assert!(spu
+            .output
+            .iter()
+            .any(|&(left, right)| left > 0 && right > 0));
+        assert_eq!(spu.load(ENDX), 0);
+
+        spu.tick(CYCLES_PER_SAMPLE * 20, &mut interrupts);
+
+        assert_eq!(spu.load(ENDX), 1);
+        assert_eq!(spu.voices[0].phase, AdsrPhase::Off);
+    }
+
+    #[test]
+    fn transfers_to_the_irq_address_raise_an_interrupt() {
+        let mut interrupts = InterruptController::new();
+        let mut spu = Spu::new();
+
+        interrupts.store(4, 1 << Interrupt::Spu as u32);
+        spu.store(IRQ_ADDRESS, 0x2000 / 8 + 1, &mut interrupts);
+        spu.store(SPUCNT, CNT_IRQ_ENABLE, &mut interrupts);
+
+        write_block(&mut spu, 0x2000, &mut interrupts);
+
+        assert!(interrupts.pending());
+        assert_ne!(spu.load(SPUSTAT) & (1 << 6), 0);
+
+        spu.store(SPUCNT, 0, &mut interrupts);
+        assert_eq!(spu.load(SPUSTAT) & (1 << 6), 0);
+    }
+
+    #[test]
+    fn any_halfword_of_the_irq_unit_raises_an_interrupt() {
+        let mut interrupts = InterruptController::new();
+        let mut spu = Spu::new();
+
+        // CD right capture buffer (0x400), samples 4 to 7
+        spu.store(IRQ_ADDRESS, 0x408 / 8, &mut interrupts);
+        spu.tick(CYCLES_PER_SAMPLE * 5, &mut interrupts);
+        spu.store(SPUCNT, CNT_IRQ_ENABLE, &mut interrupts);
+
+        assert!(!spu.irq);
+
+        // Sample 5 goes to 0x40a
+        spu.tick(CYCLES_PER_SAMPLE, &mut interrupts);
+
+        assert!(spu.irq);
+        assert_ne!(interrupts.status & (1 << Interrupt::Spu as u32), 0);
+    }
+
+    #[test]
+    fn cd_audio_is_scaled_by_its_volume_and_gated_by_spucnt() {
+        let mut interrupts = InterruptController::new();
+        let mut spu = Spu::new();
+
+        spu.store(SPUCNT, CNT_UNMUTE | CNT_CD_AUDIO, &mut interrupts);
+        spu.store(MAIN_VOLUME_LEFT, 0x3fff, &mut interrupts);
+        spu.store(MAIN_VOLUME_RIGHT, 0x3fff, &mut interrupts);
+        spu.store(CD_VOLUME_LEFT, 0x4000, &mut interrupts); // Half
+        spu.store(CD_VOLUME_RIGHT, 0x2000, &mut interrupts); // Quarter
+
+        spu.push_cd_audio(0x2000, 0x2000);
+        spu.push_cd_audio(0x2000, 0x2000);
+        spu.tick(CYCLES_PER_SAMPLE, &mut interrupts);
+
+        // 0x1000 and 0x800 through a main volume just under full
+        assert_eq!(spu.output.pop_front(), Some((0xfff, 0x7ff)));
+
+        spu.store(SPUCNT, CNT_UNMUTE, &mut interrupts);
+        spu.tick(CYCLES_PER_SAMPLE, &mut interrupts);
+
+        assert_eq!(spu.output.pop_front(), Some((0, 0)));
+        assert!(spu.cd_audio.is_empty());
+    }
+}
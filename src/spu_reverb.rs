@@ -0,0 +1,173 @@
+/// Reverb registers (0x1f801dc0 - 0x1f801dff), in halfwords. Addresses
+/// (m*, d*) are in 8-byte units relative to the buffer address, volumes
+/// (v*) are signed fractions of 0x8000.
+const D_APF1: usize = 0;
+const D_APF2: usize = 1;
+const V_IIR: usize = 2;
+const V_COMB1: usize = 3;
+const V_COMB2: usize = 4;
+const V_COMB3: usize = 5;
+const V_COMB4: usize = 6;
+const V_WALL: usize = 7;
+const V_APF1: usize = 8;
+const V_APF2: usize = 9;
+const M_LSAME: usize = 10;
+const M_RSAME: usize = 11;
+const M_LCOMB1: usize = 12;
+const M_RCOMB1: usize = 13;
+const M_LCOMB2: usize = 14;
+const M_RCOMB2: usize = 15;
+const D_LSAME: usize = 16;
+const D_RSAME: usize = 17;
+const M_LDIFF: usize = 18;
+const M_RDIFF: usize = 19;
+const M_LCOMB3: usize = 20;
+const M_RCOMB3: usize = 21;
+const M_LCOMB4: usize = 22;
+const M_RCOMB4: usize = 23;
+const D_LDIFF: usize = 24;
+const D_RDIFF: usize = 25;
+const M_LAPF1: usize = 26;
+const M_RAPF1: usize = 27;
+const M_LAPF2: usize = 28;
+const M_RAPF2: usize = 29;
+const V_LIN: usize = 30;
+const V_RIN: usize = 31;
+
+fn saturate(value: i32) -> i32 {
+    value.clamp(-0x8000, 0x7fff)
+}
+
+fn multiply(value: i32, volume: u16) -> i32 {
+    (value * volume as i16 as i32) >> 15
+}
+
+/// Reverb engine, working in a ring buffer at the top of sound RAM
+#[derive(Debug, Clone, Default)]
+pub struct Reverb {
+    pub registers: [u16; 32],
+    pub base: u16,         // mBASE, start of the work area in 8-byte units
+    pub volume_left: u16,  // vLOUT
+    pub volume_right: u16, // vROUT
+    address: u32,          // Current buffer address
+    odd: bool,             // Runs every other sample, at 22.05 kHz
+    output: (i32, i32),
+}
+
+impl Reverb {
+    pub fn set_base(&mut self, value: u16) {
+        self.base = value;
+        self.address = value as u32 * 8;
+    }
+
+    /// Byte address of the buffer entry at `register` (+ `offset` bytes),
+    /// wrapping within the work area.
+    fn buffer_address(&self, register: usize, offset: i64) -> usize {
+        let base = self.base as i64 * 8;
+        let size = 0x80000 - base;
+        let relative = self.address as i64 - base + self.registers[register] as i64 * 8 + offset;
+
+        ((base + relative.rem_euclid(size.max(2))) & 0x7fffe) as usize
+    }
+
+    fn load(&self, ram: &[u8], register: usize, offset: i64) -> i32 {
+        let address = self.buffer_address(register, offset);
+
+        i16::from_le_bytes([ram[address], ram[address + 1]]) as i32
+    }
+
+    fn store(&self, ram: &mut [u8], register: usize, value: i32) {
+        let address = self.buffer_address(register, 0);
+        let bytes = (saturate(value) as i16).to_le_bytes();
+
+        ram[address..address + 2].copy_from_slice(&bytes);
+    }
+
+    /// Mixes `value` with the buffer entry `delay` entries back, and stores
+    /// the result at the current one.
+    fn all_pass(
+        &self,
+        ram: &mut [u8],
+        value: i32,
+        buffer: usize,
+        delay: u16,
+        volume: u16,
+        enabled: bool,
+    ) -> i32 {
+        let delayed = self.load(ram, buffer, -(delay as i64 * 8));
+        let filtered = saturate(value - multiply(delayed, volume));
+
+        if enabled {
+            self.store(ram, buffer, filtered);
+        }
+
+        saturate(multiply(filtered, volume) + delayed)
+    }
+
+    /// Takes the voice (and CD) input meant for reverb and returns the
+    /// reverb output. The buffer is only written to when `enabled`.
+    pub fn process(&mut self, ram: &mut [u8], input: (i32, i32), enabled: bool) -> (i32, i32) {
+        self.odd = !self.odd;
+
+        if !self.odd {
+            return self.output;
+        }
+
+        let r = self.registers;
+        let left_in = multiply(input.0, r[V_LIN]);
+        let right_in = multiply(input.1, r[V_RIN]);
+
+        if enabled {
+            // Same side and cross side reflections
+            let reflections = [
+                (M_LSAME, D_LSAME, left_in),
+                (M_RSAME, D_RSAME, right_in),
+                (M_LDIFF, D_RDIFF, left_in),
+                (M_RDIFF, D_LDIFF, right_in),
+            ];
+
+            for &(destination, source, input) in reflections.iter() {
+                let previous = self.load(ram, destination, -2);
+                let wall = multiply(self.load(ram, source, 0), r[V_WALL]);
+                let value = multiply(input + wall - previous, r[V_IIR]) + previous;
+
+                self.store(ram, destination, value);
+            }
+        }
+
+        // Early echo
+        let comb = |combs: [usize; 4]| {
+            multiply(self.load(ram, combs[0], 0), r[V_COMB1])
+                + multiply(self.load(ram, combs[1], 0), r[V_COMB2])
+                + multiply(self.load(ram, combs[2], 0), r[V_COMB3])
+                + multiply(self.load(ram, combs[3], 0), r[V_COMB4])
+        };
+        let mut left = comb([M_LCOMB1, M_LCOMB2, M_LCOMB3, M_LCOMB4]);
+        let mut right = comb([M_RCOMB1, M_RCOMB2, M_RCOMB3, M_RCOMB4]);
+
+        // Late reverb, through two all-pass filters
+        let filters = [
+            (M_LAPF1, M_RAPF1, D_APF1, V_APF1),
+            (M_LAPF2, M_RAPF2, D_APF2, V_APF2),
+        ];
+
+        for &(left_buffer, right_buffer, delay, volume) in filters.iter() {
+            left = self.all_pass(ram, left, left_buffer, r[delay], r[volume], enabled);
+            right = self.all_pass(ram, right, right_buffer, r[delay], r[volume], enabled);
+        }
+
+        self.output = (
+            multiply(left, self.volume_left),
+            multiply(right, self.volume_right),
+        );
+
+        // Advance through the work area
+        self.address = (self.address + 2) & 0x7fffe;
+
+        if self.address < self.base as u32 * 8 {
+            self.address = self.base as u32 * 8;
+        }
+
+        self.output
+    }
+}
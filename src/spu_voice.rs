@@ -0,0 +1,304 @@
+/// ADPCM prediction filters (positive and negative coefficients, / 64)
+const FILTERS: [(i32, i32); 5] = [(0, 0), (60, 0), (115, -52), (98, -55), (122, -60)];
+
+/// Samples in a 16-byte ADPCM block
+const BLOCK_SAMPLES: u32 = 28;
+
+/// ADPCM block flags
+const FLAG_LOOP_END: u8 = 1 << 0;
+const FLAG_LOOP_REPEAT: u8 = 1 << 1;
+const FLAG_LOOP_START: u8 = 1 << 2;
+
+/// Speed and direction of an envelope: an ADSR phase or a volume sweep
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Rate {
+    pub shift: u32,
+    pub step: u32, // Encoded, +7..+4 when increasing, -8..-5 when decreasing
+    pub decreasing: bool,
+    pub exponential: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Envelope {
+    pub level: i16,
+    wait: u32, // Samples until the next step
+}
+
+impl Envelope {
+    /// Advances by one sample.
+    pub fn tick(&mut self, rate: Rate) {
+        if self.wait > 1 {
+            self.wait -= 1;
+            return;
+        }
+
+        let level = self.level as i32;
+        let mut step = if rate.decreasing {
+            -8 + rate.step as i32
+        } else {
+            7 - rate.step as i32
+        };
+        let mut wait = 1 << rate.shift.saturating_sub(11);
+
+        step <<= 11u32.saturating_sub(rate.shift);
+
+        if rate.exponential {
+            if rate.decreasing {
+                step = (step * level) >> 15;
+            } else if level > 0x6000 {
+                wait *= 4;
+            }
+        }
+
+        self.level = (level + step).clamp(0, 0x7fff) as i16;
+        self.wait = wait;
+    }
+}
+
+/// Voice or main volume: either fixed, or sweeping on its own
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Volume {
+    pub register: u16,
+    pub envelope: Envelope,
+}
+
+impl Volume {
+    pub fn set(&mut self, value: u16) {
+        self.register = value;
+
+        if value & 0x8000 == 0 {
+            self.envelope.level = (value << 1) as i16;
+        }
+    }
+
+    fn sweeping(&self) -> bool {
+        self.register & 0x8000 != 0
+    }
+
+    pub fn tick(&mut self) {
+        if self.sweeping() {
+            self.envelope.tick(Rate {
+                shift: (self.register as u32 >> 2) & 0x1f,
+                step: self.register as u32 & 0x3,
+                decreasing: self.register & (1 << 13) != 0,
+                exponential: self.register & (1 << 14) != 0,
+            });
+        }
+    }
+
+    pub fn level(&self) -> i32 {
+        let level = self.envelope.level as i32;
+
+        // Sweeps in negative phase invert the signal
+        if self.sweeping() && self.register & (1 << 12) != 0 {
+            -level
+        } else {
+            level
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum AdsrPhase {
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+    #[default]
+    Off,
+}
+
+/// One of the 24 ADPCM voices
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Voice {
+    pub volume_left: Volume,
+    pub volume_right: Volume,
+    pub pitch: u16,
+    pub start_address: u16, // In 8-byte units
+    pub adsr: u32,
+    pub repeat_address: u16, // In 8-byte units
+    pub envelope: Envelope,
+    pub phase: AdsrPhase,
+    pub end: bool,     // ENDX: reached a block flagged as loop end
+    pub irq_hit: bool, // Read the block holding the SPU IRQ address
+    pub output: i16,   // Last sample, modulates the next voice's pitch
+    address: u32,      // Byte address of the current block
+    counter: u32,      // Sample index << 12 | fraction
+    decoded: [i16; BLOCK_SAMPLES as usize],
+    history: [i16; 2], // Previous two decoded samples, for the filters
+    previous: i16,     // Sample before the first of the block
+    flags: u8,         // Flags of the current block
+}
+
+impl Voice {
+    pub fn key_on(&mut self, ram: &[u8], irq_address: u32) {
+        self.address = self.start_address as u32 * 8;
+        self.counter = 0;
+        self.history = [0; 2];
+        self.previous = 0;
+        self.phase = AdsrPhase::Attack;
+        self.envelope = Envelope::default();
+        self.end = false;
+        self.decode_block(ram, irq_address);
+    }
+
+    pub fn key_off(&mut self) {
+        if self.phase != AdsrPhase::Off {
+            self.phase = AdsrPhase::Release;
+        }
+    }
+
+    /// Decodes the block at the current address.
+    fn decode_block(&mut self, ram: &[u8], irq_address: u32) {
+        let mut block = [0; 16];
+
+        for (n, byte) in block.iter_mut().enumerate() {
+            *byte = ram[(self.address as usize + n) & 0x7ffff];
+        }
+
+        let shift = match block[0] & 0xf {
+            shift @ 0..=12 => shift,
+            _ => 9, // Reserved shifts behave like 9
+        };
+        let (positive, negative) = FILTERS[((block[0] >> 4) & 0x7).min(4) as usize];
+
+        self.flags = block[1];
+
+        if self.flags & FLAG_LOOP_START != 0 {
+            self.repeat_address = (self.address / 8) as u16;
+        }
+
+        for (n, sample) in self.decoded.iter_mut().enumerate() {
+            let nibble = (block[2 + n / 2] >> ((n & 1) * 4)) & 0xf;
+            let raw = (((nibble as i16) << 12) >> shift) as i32;
+            let prediction =
+                (self.history[0] as i32 * positive + self.history[1] as i32 * negative + 32) >> 6;
+
+            *sample = (raw + prediction).clamp(-0x8000, 0x7fff) as i16;
+            self.history = [*sample, self.history[0]];
+        }
+
+        if (self.address..self.address + 16).contains(&irq_address) {
+            self.irq_hit = true;
+        }
+    }
+
+    /// Moves on to the next block, following the loop flags of this one.
+    fn next_block(&mut self, ram: &[u8], irq_address: u32) {
+        if self.flags & FLAG_LOOP_END != 0 {
+            self.end = true;
+
+            if self.flags & FLAG_LOOP_REPEAT != 0 {
+                self.address = self.repeat_address as u32 * 8;
+            } else {
+                self.phase = AdsrPhase::Release;
+                self.envelope.level = 0;
+                self.address = (self.address + 16) & 0x7ffff;
+            }
+        } else {
+            self.address = (self.address + 16) & 0x7ffff;
+        }
+
+        self.decode_block(ram, irq_address);
+    }
+
+    /// Produces the next sample, before the voice volume. `modulation` is the
+    /// previous voice's output when pitch modulation is on, `noise` replaces
+    /// the ADPCM data when the voice plays noise.
+    pub fn sample(
+        &mut self,
+        ram: &[u8],
+        irq_address: u32,
+        modulation: Option<i16>,
+        noise: Option<i16>,
+    ) -> i32 {
+        self.volume_left.tick();
+        self.volume_right.tick();
+
+        if self.phase == AdsrPhase::Off {
+            self.output = 0;
+            return 0;
+        }
+
+        // Linear interpolation between the last two samples
+        let index = (self.counter >> 12) as usize;
+        let current = self.decoded[index] as i32;
+        let previous = match index {
+            0 => self.previous,
+            _ => self.decoded[index - 1],
+        } as i32;
+        let fraction = (self.counter & 0xfff) as i32;
+        let interpolated = previous + (((current - previous) * fraction) >> 12);
+
+        let raw = noise.map_or(interpolated, |noise| noise as i32);
+        let output = (raw * self.envelope.level as i32) >> 15;
+
+        self.output = output as i16;
+
+        let mut step = self.pitch.min(0x4000) as i32;
+
+        if let Some(modulation) = modulation {
+            step = ((step * (modulation as i32 + 0x8000)) >> 15).clamp(0, 0xffff);
+        }
+
+        self.counter += step as u32;
+
+        if self.counter >> 12 >= BLOCK_SAMPLES {
+            self.counter -= BLOCK_SAMPLES << 12;
+            self.previous = self.decoded[BLOCK_SAMPLES as usize - 1];
+            self.next_block(ram, irq_address);
+        }
+
+        self.tick_envelope();
+
+        output
+    }
+
+    fn sustain_level(&self) -> i16 {
+        (((self.adsr & 0xf) + 1) * 0x800).min(0x7fff) as i16
+    }
+
+    fn tick_envelope(&mut self) {
+        let adsr = self.adsr;
+        let rate = match self.phase {
+            AdsrPhase::Attack => Rate {
+                shift: (adsr >> 10) & 0x1f,
+                step: (adsr >> 8) & 0x3,
+                decreasing: false,
+                exponential: adsr & (1 << 15) != 0,
+            },
+            AdsrPhase::Decay => Rate {
+                shift: (adsr >> 4) & 0xf,
+                step: 0,
+                decreasing: true,
+                exponential: true,
+            },
+            AdsrPhase::Sustain => Rate {
+                shift: (adsr >> 24) & 0x1f,
+                step: (adsr >> 22) & 0x3,
+                decreasing: adsr & (1 << 30) != 0,
+                exponential: adsr & (1 << 31) != 0,
+            },
+            AdsrPhase::Release => Rate {
+                shift: (adsr >> 16) & 0x1f,
+                step: 0,
+                decreasing: true,
+                exponential: adsr & (1 << 21) != 0,
+            },
+            AdsrPhase::Off => return,
+        };
+
+        self.envelope.tick(rate);
+
+        match self.phase {
+            AdsrPhase::Attack if self.envelope.level == 0x7fff => {
+                self.phase = AdsrPhase::Decay;
+            }
+            AdsrPhase::Decay if self.envelope.level <= self.sustain_level() => {
+                self.phase = AdsrPhase::Sustain;
+            }
+            AdsrPhase::Release if self.envelope.level == 0 => self.phase = AdsrPhase::Off,
+            _ => (),
+        }
+    }
+}
@@ -0,0 +1,359 @@
+use crate::interrupt_controller::{Interrupt, InterruptController};
+
+/// Register offsets within a counter (0x1f801100 + 0x10 * counter)
+const VALUE: u32 = 0x0;
+const MODE: u32 = 0x4;
+const TARGET: u32 = 0x8;
+
+/// Mode bits
+const MODE_SYNC_ENABLE: u32 = 1 << 0;
+const MODE_RESET_ON_TARGET: u32 = 1 << 3;
+const MODE_IRQ_ON_TARGET: u32 = 1 << 4;
+const MODE_IRQ_ON_OVERFLOW: u32 = 1 << 5;
+const MODE_IRQ_REPEAT: u32 = 1 << 6;
+const MODE_IRQ_TOGGLE: u32 = 1 << 7;
+const MODE_IRQ_NOT_REQUESTED: u32 = 1 << 10; // Active low
+const MODE_REACHED_TARGET: u32 = 1 << 11; // Cleared when read
+const MODE_REACHED_OVERFLOW: u32 = 1 << 12; // Cleared when read
+const MODE_WRITABLE_MASK: u32 = 0x3ff;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClockSource {
+    SystemClock,
+    SystemClockDiv8, // Counter 2 only
+    DotClock,        // Counter 0 only
+    Hblank,          // Counter 1 only
+}
+
+/// Root counter
+#[derive(Debug, Clone, Default)]
+pub struct Timer {
+    pub index: usize,
+    pub value: u32,      // 16-bit counter
+    pub mode: u32,       // Counter mode
+    pub target: u32,     // 16-bit target value
+    irq_done: bool,      // A one-shot IRQ has already fired
+    in_blank: bool,      // Inside the blanking period the counter synchronizes to
+    system_divider: u32, // System clock cycles towards the next sysclk/8 tick
+}
+
+impl Timer {
+    fn new(index: usize) -> Self {
+        Self {
+            index,
+            mode: MODE_IRQ_NOT_REQUESTED,
+            ..Default::default()
+        }
+    }
+
+    pub fn source(&self) -> ClockSource {
+        match (self.index, (self.mode >> 8) & 0x3) {
+            (0, 1) | (0, 3) => ClockSource::DotClock,
+            (1, 1) | (1, 3) => ClockSource::Hblank,
+            (2, 2) | (2, 3) => ClockSource::SystemClockDiv8,
+            _ => ClockSource::SystemClock,
+        }
+    }
+
+    fn sync_mode(&self) -> Option<u32> {
+        if self.mode & MODE_SYNC_ENABLE != 0 {
+            Some((self.mode >> 1) & 0x3)
+        } else {
+            None
+        }
+    }
+
+    fn paused(&self) -> bool {
+        match (self.index, self.sync_mode()) {
+            (_, None) => false,
+            (2, Some(mode)) => mode == 0 || mode == 3, // Stopped for good
+            (_, Some(0)) => self.in_blank,
+            (_, Some(2)) => !self.in_blank,
+            (_, Some(3)) => true, // Until the first blanking period
+            _ => false,
+        }
+    }
+
+    /// Entering or leaving the blanking period of counters 0 (hblank) and 1
+    /// (vblank).
+    fn set_blank(&mut self, active: bool) {
+        if active && !self.in_blank {
+            match self.sync_mode() {
+                Some(1) | Some(2) => self.value = 0,
+                Some(3) => self.mode &= !MODE_SYNC_ENABLE, // Free run from now on
+                _ => (),
+            }
+        }
+
+        self.in_blank = active;
+    }
+
+    /// Counts `ticks` of the counter's clock, returns whether an interrupt
+    /// was raised.
+    fn advance(&mut self, mut ticks: u32) -> bool {
+        let mut irq = false;
+
+        if self.paused() {
+            return false;
+        }
+
+        while ticks > 0 {
+            let mut wrap = if self.mode & MODE_RESET_ON_TARGET != 0 {
+                self.target
+            } else {
+                0xffff
+            };
+
+            // Already past the target, the counter runs to 0xffff
+            if self.value > wrap {
+                wrap = 0xffff;
+            }
+
+            if self.value == wrap {
+                self.value = 0;
+                ticks -= 1;
+            } else {
+                let event = if self.value < self.target && self.target < wrap {
+                    self.target
+                } else {
+                    wrap
+                };
+                let step = ticks.min(event - self.value);
+
+                self.value += step;
+                ticks -= step;
+            }
+
+            // Wrapping to a target of 0 reaches it again on every tick
+            irq |= self.check_events();
+        }
+
+        irq
+    }
+
+    /// Flags the target and overflow values when the counter is on them,
+    /// returns whether an interrupt was raised.
+    fn check_events(&mut self) -> bool {
+        let mut irq = false;
+
+        if self.value == self.target {
+            self.mode |= MODE_REACHED_TARGET;
+
+            if self.mode & MODE_IRQ_ON_TARGET != 0 {
+                irq |= self.trigger_irq();
+            }
+        }
+
+        if self.value == 0xffff {
+            self.mode |= MODE_REACHED_OVERFLOW;
+
+            if self.mode & MODE_IRQ_ON_OVERFLOW != 0 {
+                irq |= self.trigger_irq();
+            }
+        }
+
+        irq
+    }
+
+    /// In pulse mode bit 10 only goes low for a few cycles, in toggle mode it
+    /// flips and the interrupt fires on its falling edge.
+    fn trigger_irq(&mut self) -> bool {
+        if self.mode & MODE_IRQ_REPEAT == 0 && self.irq_done {
+            return false;
+        }
+
+        self.irq_done = true;
+
+        if self.mode & MODE_IRQ_TOGGLE != 0 {
+            self.mode ^= MODE_IRQ_NOT_REQUESTED;
+            self.mode & MODE_IRQ_NOT_REQUESTED == 0
+        } else {
+            true
+        }
+    }
+
+    fn load(&mut self, offset: u32) -> u32 {
+        match offset {
+            VALUE => self.value,
+            MODE => {
+                let mode = self.mode;
+                self.mode &= !(MODE_REACHED_TARGET | MODE_REACHED_OVERFLOW);
+                mode
+            }
+            TARGET => self.target,
+            _ => 0,
+        }
+    }
+
+    fn store(&mut self, offset: u32, value: u32) {
+        match offset {
+            VALUE => self.value = value & 0xffff,
+            MODE => {
+                // Writing the mode restarts the counter
+                self.mode = (value & MODE_WRITABLE_MASK) | MODE_IRQ_NOT_REQUESTED;
+                self.value = 0;
+                self.irq_done = false;
+            }
+            TARGET => self.target = value & 0xffff,
+            _ => (),
+        }
+    }
+
+    fn interrupt(&self) -> Interrupt {
+        match self.index {
+            0 => Interrupt::Timer0,
+            1 => Interrupt::Timer1,
+            _ => Interrupt::Timer2,
+        }
+    }
+}
+
+/// The three root counters (0x1f801100 - 0x1f80112f)
+#[derive(Debug, Clone)]
+pub struct Timers {
+    pub timers: [Timer; 3],
+}
+
+impl Timers {
+    pub fn new() -> Self {
+        Self {
+            timers: [Timer::new(0), Timer::new(1), Timer::new(2)],
+        }
+    }
+
+    /// Advances the counters clocked by the system clock.
+    pub fn tick(&mut self, cycles: u32, interrupts: &mut InterruptController) {
+        for timer in self.timers.iter_mut() {
+            let ticks = match timer.source() {
+                ClockSource::SystemClock => cycles,
+                ClockSource::SystemClockDiv8 => {
+                    timer.system_divider += cycles;
+                    let ticks = timer.system_divider / 8;
+                    timer.system_divider %= 8;
+                    ticks
+                }
+                _ => continue,
+            };
+
+            if timer.advance(ticks) {
+                interrupts.request(timer.interrupt());
+            }
+        }
+    }
+
+    /// Counter 0 can count GPU dots.
+    pub fn dot_clock(&mut self, dots: u32, interrupts: &mut InterruptController) {
+        let timer = &mut self.timers[0];
+
+        if timer.source() == ClockSource::DotClock && timer.advance(dots) {
+            interrupts.request(Interrupt::Timer0);
+        }
+    }
+
+    /// Counter 0 synchronizes to hblank, which counter 1 can count.
+    pub fn set_hblank(&mut self, active: bool, interrupts: &mut InterruptController) {
+        self.timers[0].set_blank(active);
+
+        let timer = &mut self.timers[1];
+
+        if active && timer.source() == ClockSource::Hblank && timer.advance(1) {
+            interrupts.request(Interrupt::Timer1);
+        }
+    }
+
+    /// Counter 1 synchronizes to vblank.
+    pub fn set_vblank(&mut self, active: bool) {
+        self.timers[1].set_blank(active);
+    }
+
+    pub fn load(&mut self, offset: u32) -> u32 {
+        match self.timers.get_mut((offset >> 4) as usize) {
+            Some(timer) => timer.load(offset & 0xf),
+            None => 0,
+        }
+    }
+
+    pub fn store(&mut self, offset: u32, value: u32) {
+        if let Some(timer) = self.timers.get_mut((offset >> 4) as usize) {
+            timer.store(offset & 0xf, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Counter 2 registers, it counts the system clock by default
+    const TIMER_2: u32 = 0x20;
+
+    fn timer_2_interrupts(interrupts: &mut InterruptController) -> bool {
+        let requested = interrupts.status & (1 << Interrupt::Timer2 as u32) != 0;
+
+        interrupts.status = 0;
+        requested
+    }
+
+    #[test]
+    fn zero_target_with_reset_is_reached_every_tick() {
+        let mut timers = Timers::new();
+        let mut interrupts = InterruptController::new();
+        let mode = MODE_RESET_ON_TARGET | MODE_IRQ_ON_TARGET | MODE_IRQ_REPEAT;
+
+        timers.store(TIMER_2 + TARGET, 0);
+        timers.store(TIMER_2 + MODE, mode);
+
+        for _ in 0..3 {
+            timers.tick(1, &mut interrupts);
+
+            assert!(timer_2_interrupts(&mut interrupts));
+            assert_eq!(timers.load(TIMER_2 + VALUE), 0);
+            assert_ne!(timers.load(TIMER_2 + MODE) & MODE_REACHED_TARGET, 0);
+        }
+    }
+
+    #[test]
+    fn one_shot_fires_once_and_repeat_every_time() {
+        for &repeat in [false, true].iter() {
+            let mut timers = Timers::new();
+            let mut interrupts = InterruptController::new();
+            let mut mode = MODE_RESET_ON_TARGET | MODE_IRQ_ON_TARGET;
+
+            if repeat {
+                mode |= MODE_IRQ_REPEAT;
+            }
+
+            timers.store(TIMER_2 + TARGET, 10);
+            timers.store(TIMER_2 + MODE, mode);
+            timers.tick(10, &mut interrupts);
+
+            assert!(timer_2_interrupts(&mut interrupts));
+
+            // Back to 0, then up to the target again
+            timers.tick(11, &mut interrupts);
+
+            assert_eq!(timer_2_interrupts(&mut interrupts), repeat);
+            assert_eq!(timers.load(TIMER_2 + VALUE), 10);
+        }
+    }
+
+    #[test]
+    fn toggle_mode_fires_on_every_other_target() {
+        let mut timers = Timers::new();
+        let mut interrupts = InterruptController::new();
+        let mode = MODE_RESET_ON_TARGET | MODE_IRQ_ON_TARGET | MODE_IRQ_REPEAT | MODE_IRQ_TOGGLE;
+
+        timers.store(TIMER_2 + TARGET, 4);
+        timers.store(TIMER_2 + MODE, mode);
+
+        let mut fired = Vec::new();
+
+        for _ in 0..4 {
+            timers.tick(5, &mut interrupts);
+            fired.push(timer_2_interrupts(&mut interrupts));
+        }
+
+        assert_eq!(fired, [true, false, true, false]);
+        assert_ne!(timers.load(TIMER_2 + MODE) & MODE_IRQ_NOT_REQUESTED, 0);
+    }
+}
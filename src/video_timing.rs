@@ -0,0 +1,273 @@
+use crate::{
+    gpu::Gpu,
+    interrupt_controller::{Interrupt, InterruptController},
+    timers::Timers,
+};
+
+/// The GPU runs at 11/7 of the CPU clock
+const GPU_CLOCKS_PER_7_CYCLES: u32 = 11;
+
+/// GPU clocks per scanline
+const NTSC_LINE_CLOCKS: u32 = 3413;
+const PAL_LINE_CLOCKS: u32 = 3406;
+
+/// Scanlines per frame, or per odd field when interlaced
+const NTSC_LINES: u32 = 263;
+const PAL_LINES: u32 = 314;
+
+/// Beam position, derived from the CPU cycle count
+#[derive(Debug, Clone, Default)]
+pub struct VideoTiming {
+    pub line: u32,         // Scanline being output
+    pub line_clock: u32,   // GPU clocks into the scanline
+    pub frame_ended: bool, // Last line output, cleared by the frontend
+    clock_remainder: u32,  // CPU cycles * 11 not yet worth a GPU clock
+    dot_remainder: u32,    // GPU clocks towards the next dot
+    in_hblank: bool,
+    in_vblank: bool,
+}
+
+impl VideoTiming {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn tick(
+        &mut self,
+        cycles: u32,
+        gpu: &mut Gpu,
+        timers: &mut Timers,
+        interrupts: &mut InterruptController,
+    ) {
+        let total = cycles * GPU_CLOCKS_PER_7_CYCLES + self.clock_remainder;
+        let mut clocks = total / 7;
+        self.clock_remainder = total % 7;
+
+        let divider = gpu.dot_clock_divider();
+        let dots = (self.dot_remainder + clocks) / divider;
+        self.dot_remainder = (self.dot_remainder + clocks) % divider;
+
+        if dots > 0 {
+            timers.dot_clock(dots, interrupts);
+        }
+
+        let line_clocks = if gpu.pal() {
+            PAL_LINE_CLOCKS
+        } else {
+            NTSC_LINE_CLOCKS
+        };
+
+        // Step from one hblank edge or end of line to the next
+        while clocks > 0 {
+            let next_event = [gpu.display_range_x1, gpu.display_range_x2, line_clocks]
+                .iter()
+                .copied()
+                .filter(|&event| event > self.line_clock)
+                .min()
+                .unwrap_or(line_clocks)
+                .min(line_clocks);
+            let step = clocks.min(next_event - self.line_clock);
+
+            self.line_clock += step;
+            clocks -= step;
+
+            if self.line_clock >= line_clocks {
+                self.line_clock = 0;
+                self.next_line(gpu, timers, interrupts);
+            }
+
+            let hblank =
+                self.line_clock < gpu.display_range_x1 || self.line_clock >= gpu.display_range_x2;
+
+            if hblank != self.in_hblank {
+                self.in_hblank = hblank;
+                timers.set_hblank(hblank, interrupts);
+            }
+        }
+    }
+
+    fn next_line(
+        &mut self,
+        gpu: &mut Gpu,
+        timers: &mut Timers,
+        interrupts: &mut InterruptController,
+    ) {
+        let mut lines = if gpu.pal() { PAL_LINES } else { NTSC_LINES };
+
+        // Interlaced fields alternate between 262 and 263 lines (313 and 314)
+        if gpu.interlaced() && !gpu.field {
+            lines -= 1;
+        }
+
+        self.line += 1;
+
+        // Frames end on their last line whatever the display range, so odd
+        // GP1(07) values cannot stop the vblank interrupt
+        if self.line >= lines {
+            self.line = 0;
+            interrupts.request(Interrupt::Vblank);
+            gpu.start_frame();
+            self.frame_ended = true;
+        }
+
+        // The display range only shapes the blanking seen by GPUSTAT and
+        // counter 1
+        let vblank = self.line < gpu.display_range_y1 || self.line >= gpu.display_range_y2;
+
+        if vblank != self.in_vblank {
+            self.in_vblank = vblank;
+            timers.set_vblank(vblank);
+        }
+
+        gpu.set_line(self.line, vblank);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// GP1(08) display mode bits
+    const PAL: u32 = 1 << 3;
+    const INTERLACED: u32 = 1 << 5;
+
+    /// Ticks of 64 CPU cycles, shorter than a scanline, in a few frames
+    const MAX_TICKS: usize = 40000;
+
+    /// Video timing along with the devices it drives
+    struct Video {
+        timing: VideoTiming,
+        gpu: Gpu,
+        timers: Timers,
+        interrupts: InterruptController,
+    }
+
+    impl Video {
+        fn new(display_mode: u32) -> Self {
+            let mut gpu = Gpu::new();
+
+            gpu.gp1(0x08000000 | display_mode);
+
+            Self {
+                timing: VideoTiming::new(),
+                gpu,
+                timers: Timers::new(),
+                interrupts: InterruptController::new(),
+            }
+        }
+
+        fn tick(&mut self, cycles: u32) {
+            self.timing.tick(
+                cycles,
+                &mut self.gpu,
+                &mut self.timers,
+                &mut self.interrupts,
+            );
+        }
+
+        /// Runs to the end of the frame, returns the scanlines output and
+        /// the vblank interrupts raised.
+        fn run_frame(&mut self) -> (u32, u32) {
+            let mut line = self.timing.line;
+            let mut lines = 0;
+            let mut vblanks = 0;
+
+            for _ in 0..MAX_TICKS {
+                self.tick(64);
+
+                if self.timing.line != line {
+                    line = self.timing.line;
+                    lines += 1;
+                }
+
+                if self.interrupts.status & (1 << Interrupt::Vblank as u32) != 0 {
+                    self.interrupts.status = 0;
+                    vblanks += 1;
+                }
+
+                if self.timing.frame_ended {
+                    self.timing.frame_ended = false;
+                    return (lines, vblanks);
+                }
+            }
+
+            panic!("The frame never ended");
+        }
+
+        /// Runs until the beam enters `line`.
+        fn run_to_line(&mut self, line: u32) {
+            while self.timing.line != line {
+                self.tick(64);
+            }
+        }
+    }
+
+    #[test]
+    fn progressive_frames_have_a_fixed_line_count() {
+        let mut ntsc = Video::new(0);
+        let mut pal = Video::new(PAL);
+
+        for _ in 0..3 {
+            assert_eq!(ntsc.run_frame(), (263, 1));
+            assert_eq!(pal.run_frame(), (314, 1));
+        }
+    }
+
+    #[test]
+    fn interlaced_fields_alternate_in_length() {
+        let mut ntsc = Video::new(INTERLACED);
+        let mut pal = Video::new(PAL | INTERLACED);
+
+        for _ in 0..2 {
+            assert_eq!(ntsc.run_frame(), (262, 1));
+            assert_eq!(ntsc.run_frame(), (263, 1));
+            assert_eq!(pal.run_frame(), (313, 1));
+            assert_eq!(pal.run_frame(), (314, 1));
+        }
+    }
+
+    #[test]
+    fn frames_end_whatever_the_display_range() {
+        let ranges = [
+            (200, 100), // Inverted
+            (100, 100), // Empty
+            (0, 0x3ff), // Every line
+        ];
+
+        for &(y1, y2) in ranges.iter() {
+            let mut video = Video::new(0);
+
+            video.gpu.gp1(0x07000000 | y1 | (y2 << 10));
+
+            for _ in 0..2 {
+                assert_eq!(video.run_frame(), (263, 1), "{} - {}", y1, y2);
+            }
+        }
+    }
+
+    #[test]
+    fn hblank_and_dots_feed_the_counters() {
+        let mut video = Video::new(0);
+
+        // Counter 0 on the dot clock, counter 1 on hblank
+        video.timers.store(0x04, 0x100);
+        video.timers.store(0x14, 0x100);
+
+        video.run_to_line(1);
+        let hblanks = video.timers.load(0x10);
+        video.run_to_line(11);
+
+        assert_eq!(video.timers.load(0x10) - hblanks, 10);
+
+        // 11000 GPU clocks, 10 per dot at 256 pixels and 4 at 640
+        video.timers.store(0x00, 0);
+        video.tick(7000);
+
+        assert_eq!(video.timers.load(0x00), 1100);
+
+        video.gpu.gp1(0x08000003);
+        video.tick(7000);
+
+        assert_eq!(video.timers.load(0x00), 1100 + 2750);
+    }
+}
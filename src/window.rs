@@ -0,0 +1,88 @@
+use crate::{
+    gpu::Frame,
+    joypad::{Button, Pad},
+};
+use minifb::{Key, ScaleMode, WindowOptions};
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Keyboard layout of the digital pad
+const KEY_MAP: [(Key, Button); 14] = [
+    (Key::Up, Button::Up),
+    (Key::Down, Button::Down),
+    (Key::Left, Button::Left),
+    (Key::Right, Button::Right),
+    (Key::Z, Button::Cross),
+    (Key::X, Button::Circle),
+    (Key::A, Button::Square),
+    (Key::S, Button::Triangle),
+    (Key::Q, Button::L1),
+    (Key::W, Button::R1),
+    (Key::E, Button::L2),
+    (Key::R, Button::R2),
+    (Key::Enter, Button::Start),
+    (Key::Backspace, Button::Select),
+];
+
+/// Frame rates of the two video standards
+const NTSC_FRAME_RATE: f64 = 59.94;
+const PAL_FRAME_RATE: f64 = 50.0;
+
+/// Desktop frontend, presenting the display area in a window
+pub struct Window {
+    window: minifb::Window,
+    next_frame: Instant, // When the next frame is due, for throttling
+}
+
+impl Window {
+    pub fn new() -> Result<Self, minifb::Error> {
+        let options = WindowOptions {
+            resize: true,
+            scale_mode: ScaleMode::AspectRatioStretch,
+            ..WindowOptions::default()
+        };
+
+        Ok(Self {
+            window: minifb::Window::new("Rust Station 1", 640, 480, options)?,
+            next_frame: Instant::now(),
+        })
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.window.is_open() && !self.window.is_key_down(Key::Escape)
+    }
+
+    /// Shows a frame and polls the keyboard.
+    pub fn present(&mut self, frame: &Frame) -> Result<(), minifb::Error> {
+        if frame.width == 0 || frame.height == 0 {
+            self.window.update();
+            return Ok(());
+        }
+
+        self.window
+            .update_with_buffer(&frame.pixels, frame.width, frame.height)
+    }
+
+    pub fn update_pad(&self, pad: &mut Pad) {
+        for &(key, button) in KEY_MAP.iter() {
+            pad.set_button(button, self.window.is_key_down(key));
+        }
+    }
+
+    /// Sleeps until the next frame is due, so emulation runs in real time.
+    pub fn throttle(&mut self, pal: bool) {
+        let frame_rate = if pal { PAL_FRAME_RATE } else { NTSC_FRAME_RATE };
+        let now = Instant::now();
+
+        self.next_frame += Duration::from_secs_f64(1.0 / frame_rate);
+
+        if self.next_frame > now {
+            thread::sleep(self.next_frame - now);
+        } else {
+            // Too far behind to catch up, start over from now
+            self.next_frame = now;
+        }
+    }
+}